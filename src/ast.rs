@@ -0,0 +1,517 @@
+use std::fmt::Display;
+
+/// How holes are spelled when a partial regex is printed.
+pub const HOLE: &str = r"\x00";
+
+/// A (possibly partial) regular expression. `Hole` marks a position that the search has not
+/// filled yet; holes are filled leftmost-first, in the order they appear in the rendered regex.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Node {
+    Hole,
+    Literal(char),
+    Concat(Box<Node>, Box<Node>),
+    Alt(Vec<Node>),
+    Star(Box<Node>),
+    Plus(Box<Node>),
+    Optional(Box<Node>),
+    /// `.`, any single symbol.
+    Any,
+    /// A character class such as `[01]`.
+    Class(Vec<char>),
+}
+
+impl Node {
+    pub fn concat(left: Node, right: Node) -> Node {
+        Node::Concat(Box::new(left), Box::new(right))
+    }
+
+    pub fn star(inner: Node) -> Node {
+        Node::Star(Box::new(inner))
+    }
+
+    pub fn plus(inner: Node) -> Node {
+        Node::Plus(Box::new(inner))
+    }
+
+    pub fn optional(inner: Node) -> Node {
+        Node::Optional(Box::new(inner))
+    }
+
+    pub fn alt(left: Node, right: Node) -> Node {
+        Node::Alt(vec![left, right])
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.holes() == 0
+    }
+
+    pub fn holes(&self) -> usize {
+        match self {
+            Node::Hole => 1,
+            Node::Literal(_) | Node::Any | Node::Class(_) => 0,
+            Node::Concat(left, right) => left.holes() + right.holes(),
+            Node::Alt(branches) => branches.iter().map(Node::holes).sum(),
+            Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => inner.holes(),
+        }
+    }
+
+    /// Whether this is the one spelling the search keeps among rewrites that accept the same
+    /// language at no higher cost: concatenations nest to the right, complete neighbouring
+    /// alternation branches are strictly increasing, and no repetition directly wraps another
+    /// apart from `((x)+)?` and `((x)?)+`, which would otherwise need a `*`.
+    pub fn is_canonical(&self) -> bool {
+        match self {
+            Node::Hole | Node::Literal(_) | Node::Any | Node::Class(_) => true,
+            Node::Concat(left, right) => {
+                !matches!(**left, Node::Concat(..)) && left.is_canonical() && right.is_canonical()
+            }
+            Node::Alt(branches) => {
+                branches.windows(2).all(|pair: &[Node]| {
+                    !(pair[0].is_complete() && pair[1].is_complete()) || pair[0] < pair[1]
+                }) && branches.iter().all(Node::is_canonical)
+            }
+            Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => {
+                let nested: bool = match (self, &**inner) {
+                    (Node::Plus(_), Node::Optional(_)) | (Node::Optional(_), Node::Plus(_)) => {
+                        false
+                    }
+                    (_, Node::Star(_) | Node::Plus(_) | Node::Optional(_)) => true,
+                    _ => false,
+                };
+                !nested && inner.is_canonical()
+            }
+        }
+    }
+
+    /// Estimated heap and inline bytes held by this tree.
+    pub fn footprint(&self) -> usize {
+        let children: usize = match self {
+            Node::Hole | Node::Literal(_) | Node::Any => 0,
+            Node::Class(symbols) => symbols.capacity() * std::mem::size_of::<char>(),
+            Node::Concat(left, right) => left.footprint() + right.footprint(),
+            Node::Alt(branches) => branches.iter().map(Node::footprint).sum(),
+            Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => inner.footprint(),
+        };
+        std::mem::size_of::<Node>() + children
+    }
+
+    /// Returns a copy with the leftmost hole replaced by `sub`, or `None` if there is no hole.
+    /// An alternation filling a branch of another alternation is spliced into it, e.g.
+    /// `(\x00|1)` becomes `(\x00|\x00|1)` rather than `((\x00|\x00)|1)`.
+    pub fn fill(&self, sub: &Node) -> Option<Node> {
+        let mut filled: Node = self.clone();
+        if filled.fill_mut(sub) {
+            Some(filled)
+        } else {
+            None
+        }
+    }
+
+    fn fill_mut(&mut self, sub: &Node) -> bool {
+        match self {
+            Node::Hole => {
+                *self = sub.clone();
+                true
+            }
+            Node::Literal(_) | Node::Any | Node::Class(_) => false,
+            Node::Concat(left, right) => left.fill_mut(sub) || right.fill_mut(sub),
+            Node::Alt(branches) => {
+                for i in 0..branches.len() {
+                    if let (Node::Hole, Node::Alt(sub_branches)) = (&branches[i], sub) {
+                        branches.splice(i..i + 1, sub_branches.iter().cloned());
+                        return true;
+                    }
+                    if branches[i].fill_mut(sub) {
+                        return true;
+                    }
+                }
+                false
+            }
+            Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => inner.fill_mut(sub),
+        }
+    }
+
+    /// Renders the expression in regex syntax, writing `hole` wherever a hole remains.
+    pub fn render(&self, hole: &str) -> String {
+        let mut out: String = String::new();
+        self.render_into(&mut out, hole);
+        out
+    }
+
+    fn render_into(&self, out: &mut String, hole: &str) {
+        match self {
+            Node::Hole => out.push_str(hole),
+            Node::Literal(c) => out.push_str(&regex::escape(&c.to_string())),
+            Node::Concat(left, right) => {
+                left.render_into(out, hole);
+                right.render_into(out, hole);
+            }
+            Node::Alt(branches) => {
+                out.push('(');
+                for (i, branch) in branches.iter().enumerate() {
+                    if i > 0 {
+                        out.push('|');
+                    }
+                    branch.render_into(out, hole);
+                }
+                out.push(')');
+            }
+            Node::Star(inner) => {
+                out.push('(');
+                inner.render_into(out, hole);
+                out.push_str(")*");
+            }
+            Node::Plus(inner) => {
+                out.push('(');
+                inner.render_into(out, hole);
+                out.push_str(")+");
+            }
+            Node::Optional(inner) => {
+                out.push('(');
+                inner.render_into(out, hole);
+                out.push_str(")?");
+            }
+            Node::Any => out.push('.'),
+            Node::Class(symbols) => {
+                out.push('[');
+                for c in symbols {
+                    out.push_str(&regex::escape(&c.to_string()));
+                }
+                out.push(']');
+            }
+        }
+    }
+
+    /// Renders the expression anchored to the whole input, ready for `Regex::new`.
+    pub fn to_regex(&self, hole: &str) -> String {
+        format!("^{}$", self.render(hole))
+    }
+
+    /// Whether the whole of `x` is in the language, reading each hole as any string over
+    /// `holes`, or as no string at all when `holes` is `None`. This agrees with matching the
+    /// rendered regex but compiles nothing, so it suits partial regexes that are checked once.
+    pub fn accepts(&self, x: &str, holes: Option<&[char]>) -> bool {
+        let x: Vec<char> = x.chars().collect();
+        let mut starts: Vec<bool> = vec![false; x.len() + 1];
+        starts[0] = true;
+        self.ends(&x, &starts, holes)[x.len()]
+    }
+
+    /// The positions in `x` where a match of this expression starting at one of `starts` can
+    /// end.
+    fn ends(&self, x: &[char], starts: &[bool], holes: Option<&[char]>) -> Vec<bool> {
+        let step = |matches: &dyn Fn(char) -> bool| -> Vec<bool> {
+            let mut ends: Vec<bool> = vec![false; starts.len()];
+            for (i, c) in x.iter().enumerate() {
+                ends[i + 1] = starts[i] && matches(*c);
+            }
+            ends
+        };
+        match self {
+            Node::Hole => match holes {
+                None => vec![false; starts.len()],
+                Some(symbols) => {
+                    let mut ends: Vec<bool> = starts.to_vec();
+                    for (i, c) in x.iter().enumerate() {
+                        ends[i + 1] |= ends[i] && symbols.contains(c);
+                    }
+                    ends
+                }
+            },
+            Node::Literal(l) => step(&|c: char| c == *l),
+            // as in `regex`, `.` is any symbol but a newline
+            Node::Any => step(&|c: char| c != '\n'),
+            Node::Class(symbols) => step(&|c: char| symbols.contains(&c)),
+            Node::Concat(left, right) => right.ends(x, &left.ends(x, starts, holes), holes),
+            Node::Alt(branches) => {
+                let mut ends: Vec<bool> = vec![false; starts.len()];
+                for branch in branches {
+                    for (end, reached) in ends.iter_mut().zip(branch.ends(x, starts, holes)) {
+                        *end |= reached;
+                    }
+                }
+                ends
+            }
+            Node::Star(inner) => inner.repeat(x, starts.to_vec(), holes),
+            Node::Plus(inner) => inner.repeat(x, inner.ends(x, starts, holes), holes),
+            Node::Optional(inner) => {
+                let mut ends: Vec<bool> = inner.ends(x, starts, holes);
+                for (end, start) in ends.iter_mut().zip(starts) {
+                    *end |= *start;
+                }
+                ends
+            }
+        }
+    }
+
+    /// `reached` together with every position a further match of this expression gets to.
+    fn repeat(&self, x: &[char], mut reached: Vec<bool>, holes: Option<&[char]>) -> Vec<bool> {
+        let mut frontier: Vec<bool> = reached.clone();
+        while frontier.contains(&true) {
+            frontier = self.ends(x, &frontier, holes);
+            for (new, old) in frontier.iter_mut().zip(reached.iter_mut()) {
+                *new &= !*old;
+                *old |= *new;
+            }
+        }
+        reached
+    }
+
+    /// Parses the syntax produced by `render`/`Display`: literals (escaped when they are
+    /// metacharacters), `\x00` holes, `.`, classes with optional ranges, `*`/`+`/`?` and
+    /// `|`. The surrounding `^`/`$` anchors are optional; concatenations nest to the right.
+    pub fn parse(regexp: &str) -> Result<Node, String> {
+        let body: &str = regexp.strip_prefix('^').unwrap_or(regexp);
+        let body: &str = body.strip_suffix('$').unwrap_or(body);
+        let mut parser: Parser = Parser {
+            chars: body.chars().collect(),
+            pos: 0,
+        };
+        let node: Node = parser.alternation()?;
+        match parser.peek() {
+            None => Ok(node),
+            Some(c) => Err(format!("unexpected '{}' at {}", c, parser.pos)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c: Option<char> = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(format!("expected '{}' at {}", expected, self.pos - 1)),
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches: Vec<Node> = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concatenation()?);
+        }
+
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alt(branches))
+        }
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut factors: Vec<Node> = Vec::new();
+        while !matches!(self.peek(), None | Some('|') | Some(')')) {
+            factors.push(self.repetition()?);
+        }
+
+        let last: Node = factors
+            .pop()
+            .ok_or_else(|| format!("empty expression at {}", self.pos))?;
+        Ok(factors
+            .into_iter()
+            .rev()
+            .fold(last, |right: Node, left: Node| Node::concat(left, right)))
+    }
+
+    fn repetition(&mut self) -> Result<Node, String> {
+        let mut node: Node = self.atom()?;
+        loop {
+            node = match self.peek() {
+                Some('*') => Node::star(node),
+                Some('+') => Node::plus(node),
+                Some('?') => Node::optional(node),
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                let node: Node = self.alternation()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some('.') => Ok(Node::Any),
+            Some('[') => self.class(),
+            Some('\\') => self.escape(),
+            Some(c) if "*+?{}[]^$".contains(c) => {
+                Err(format!("unexpected '{}' at {}", c, self.pos - 1))
+            }
+            Some(c) => Ok(Node::Literal(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        if self.chars[self.pos..].starts_with(&['x', '0', '0']) {
+            self.pos += 3;
+            return Ok(Node::Hole);
+        }
+        match self.next() {
+            Some(c) if !c.is_alphanumeric() => Ok(Node::Literal(c)),
+            Some(c) => Err(format!("unsupported escape '\\{}' at {}", c, self.pos - 2)),
+            None => Err("dangling '\\' at end of pattern".to_string()),
+        }
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        if self.peek() == Some('^') {
+            return Err(format!("unsupported negated class at {}", self.pos - 1));
+        }
+        let mut symbols: Vec<char> = Vec::new();
+        loop {
+            let c: char = match self.next() {
+                Some(']') if !symbols.is_empty() => return Ok(Node::Class(symbols)),
+                Some('\\') => self
+                    .next()
+                    .ok_or_else(|| "unterminated class".to_string())?,
+                Some(c) => c,
+                None => return Err("unterminated class".to_string()),
+            };
+            if self.peek() == Some('-') && !matches!(self.chars.get(self.pos + 1), None | Some(']'))
+            {
+                self.pos += 1;
+                let end: char = match self.next() {
+                    Some('\\') => self
+                        .next()
+                        .ok_or_else(|| "unterminated class".to_string())?,
+                    Some(end) => end,
+                    None => return Err("unterminated class".to_string()),
+                };
+                if end < c {
+                    return Err(format!("invalid class range '{}-{}'", c, end));
+                }
+                symbols.extend(c..=end);
+            } else {
+                symbols.push(c);
+            }
+        }
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_regex(HOLE))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grammar::Grammar;
+    use crate::utils::{self, State};
+
+    #[test]
+    fn alt_inside_alt_is_spliced() {
+        let s: State = State::new(
+            4,
+            Node::star(Node::star(Node::Alt(vec![
+                Node::Literal('0'),
+                Node::Hole,
+                Node::Hole,
+            ]))),
+            Vec::new(),
+        );
+        let filled: Node = s.regexp.fill(&Node::alt(Node::Hole, Node::Hole)).unwrap();
+        assert_eq!(filled.to_string(), r"^(((0|\x00|\x00|\x00))*)*$");
+        assert_eq!(filled.holes(), 3);
+    }
+
+    #[test]
+    fn parse_inverts_display() {
+        for regexp in [r"^((1)*0)*1$", r"^((0|\x00|1[a\-c]))?$", r"^a\.(\.)*(.)+$"] {
+            assert_eq!(Node::parse(regexp).unwrap().to_string(), regexp);
+        }
+        assert_eq!(
+            Node::parse("[0-2]").unwrap(),
+            Node::Class(vec!['0', '1', '2'])
+        );
+        assert!(Node::parse(r"\d").is_err());
+        assert!(Node::parse("[^0]").is_err());
+        assert_eq!(Node::parse(r"[\^0]").unwrap(), Node::Class(vec!['^', '0']));
+    }
+
+    #[test]
+    fn accepts_agrees_with_the_rendered_regex() {
+        let mut strings: Vec<String> = vec![String::new()];
+        for len in 1..=5 {
+            for bits in 0..1 << len {
+                strings.push((0..len).map(|i| ['0', '1'][bits >> i & 1]).collect());
+            }
+        }
+        for regexp in [
+            r"((1)*0)*1",
+            r"0(\x00)*1",
+            r"(\x00|10)+\x00",
+            r"((0)?1|\x00)*",
+            r"\x00[01]((.)+)?0",
+            r"(((0)*)*)+",
+        ] {
+            let node: Node = Node::parse(regexp).unwrap();
+            let sigma_star: regex::Regex = regex::Regex::new(&node.to_regex("[01]*")).unwrap();
+            let empty: regex::Regex = regex::Regex::new(&node.to_regex("$.")).unwrap();
+            for x in &strings {
+                assert_eq!(
+                    node.accepts(x, Some(&['0', '1'])),
+                    sigma_star.is_match(x),
+                    "{} on {:?}",
+                    regexp,
+                    x
+                );
+                assert_eq!(node.accepts(x, None), empty.is_match(x), "{}", regexp);
+            }
+        }
+        // a hole only stands for strings over the symbols given
+        assert!(!Node::Hole.accepts("01", Some(&['0'])));
+        assert!(Node::Hole.accepts("", Some(&[])));
+    }
+
+    #[test]
+    fn only_canonical_forms_are_enumerated() {
+        let canonical = |regexp: &str| Node::parse(regexp).unwrap().is_canonical();
+        assert!(canonical(r"(0|1)"));
+        assert!(!canonical(r"(1|0)"));
+        assert!(!canonical(r"(0|0)"));
+        // an unfinished branch cannot be ordered yet
+        assert!(canonical(r"(1|\x00)"));
+        assert!(!canonical(r"((0)*)*"));
+        assert!(!canonical(r"((0)?)*"));
+        assert!(canonical(r"((0)+)?"));
+        let left_nested: Node = Node::concat(
+            Node::concat(Node::Literal('0'), Node::Literal('1')),
+            Node::Hole,
+        );
+        assert!(!left_nested.is_canonical());
+        assert!(Node::parse(r"01\x00").unwrap().is_canonical());
+
+        assert!(Grammar::default().is_elementary());
+        let compound: Grammar = Grammar::from_toml(
+            "literal_cost = 1\n[[production]]\npattern = '(\\x00)*\\x00'\ncost = 1\n",
+        )
+        .unwrap();
+        assert!(!compound.is_elementary());
+
+        // `get_cost` still prices regexes that are not in canonical form
+        let g: Grammar = Grammar::default();
+        assert_eq!(
+            utils::get_cost(&Node::parse("^(1|0)$").unwrap(), &['0', '1'], &g, None),
+            utils::get_cost(&Node::parse("^(0|1)$").unwrap(), &['0', '1'], &g, None)
+        );
+    }
+}
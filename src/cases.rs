@@ -0,0 +1,141 @@
+use regexsynth::utils::TestCase;
+
+/// The example tasks shipped with the crate, by name, in the order `bench` runs them.
+pub fn builtin() -> Vec<(&'static str, TestCase)> {
+    // let neg_set_len: usize = 1000;
+    // let generator: ExampleGenerator = ExampleGenerator::new(['0', '1']);
+    let start_with_0: TestCase = TestCase::new(
+        ["01", "01101", "0001"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+        // generator.negatives(|x| x.starts_with('0'), neg_set_len).unwrap(),
+        ["10", "1", "11010"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+    );
+    let end_with_01: TestCase = TestCase::new(
+        ["101", "001101101", "0110001"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+        // generator.negatives(|x| x.ends_with("01"), neg_set_len).unwrap(),
+        ["100101011", "110000", "00111010"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+    );
+    let begin_with_1_and_end_with_0: TestCase = TestCase::new(
+        [
+            "11101001010010101000",
+            "100101001011101011100",
+            "10010111010010100010",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+        // generator.negatives(|x| x.starts_with('1') && x.ends_with('0'), neg_set_len).unwrap(),
+        [
+            "101001010010101000111",
+            "00010101010100100010110",
+            "00010101010101001011",
+            "0011010100110000001111010100",
+            "1001010010101011111111001011",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+    );
+    let containing_substring_0101: TestCase = TestCase::new(
+        ["0101", "00101001", "000101111"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+        // generator.negatives(|x| x.contains("0101"), neg_set_len).unwrap(),
+        ["10", "1", "11010", "1001100", "00100010", "0110110"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+    );
+    let have_at_most_two_0s: TestCase = TestCase::new(
+        [
+            "0101",
+            "1111011111011111111111111111111",
+            "111110111111111111011111111",
+            "00",
+            "011111111",
+            "11110",
+            "11111",
+            "1110111",
+            "1100",
+            "011",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+        [
+            "000",
+            "1101100101001010010",
+            "11010",
+            "1001100",
+            "00100010",
+            "0110110",
+            "000000010000",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+    );
+    let length_is_at_least_3_and_the_third_symbol_is_0: TestCase = TestCase::new(
+        ["110", "0100010100", "000111"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+        ["10", "101100", "0", "111000"]
+            .iter()
+            .map(|&x| x.to_string())
+            .collect(),
+    );
+    let each_0_is_followed_by_at_least_one_1: TestCase = TestCase::new(
+        [
+            "01",
+            "1010111011101011101011101",
+            "01011011101111011111",
+            "011010111",
+            "11010110101111",
+            "01111",
+            "1101",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+        [
+            "0000",
+            "01110001011",
+            "011010000",
+            "0110001",
+            "0001011010",
+            "00101100100",
+        ]
+        .iter()
+        .map(|&x| x.to_string())
+        .collect(),
+    );
+
+    vec![
+        ("start_with_0", start_with_0),
+        ("end_with_01", end_with_01), //^((1)*0)*1$
+        ("begin_with_1_and_end_with_0", begin_with_1_and_end_with_0),
+        ("containing_substring_0101", containing_substring_0101),
+        ("have_at_most_two_0s", have_at_most_two_0s),
+        (
+            "length_is_at_least_3_and_the_third_symbol_is_0",
+            length_is_at_least_3_and_the_third_symbol_is_0,
+        ),
+        (
+            "each_0_is_followed_by_at_least_one_1",
+            each_0_is_followed_by_at_least_one_1,
+        ),
+    ]
+}
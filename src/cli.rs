@@ -0,0 +1,592 @@
+use crate::cases;
+use crate::regex_bencharking;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use regexsynth::ast::Node;
+use regexsynth::cost::CostModel;
+use regexsynth::grammar::{ExtraProductions, Grammar};
+use regexsynth::outcome::{SynthError, SynthOutcome, SynthReport};
+#[cfg(feature = "profiling")]
+use regexsynth::profile::PhaseReport;
+use regexsynth::utils::{self, Limits, SearchOptions, Solutions, TestCase};
+use std::collections::BTreeSet;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+/// How long `bench` gives each task when no limit is set.
+const BENCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many derivations `cost` tries when the grammar cannot be priced node by node.
+const COST_MAX_STATES: usize = 1_000_000;
+
+/// Synthesizes the cheapest regular expression that accepts every positive example and
+/// rejects every negative one.
+#[derive(Parser, Debug)]
+#[command(name = "regexsynth", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    #[cfg(feature = "profiling")]
+    #[command(flatten)]
+    pub profile: ProfileArgs,
+}
+
+/// Where to write the `flame` spans recorded during the run.
+#[cfg(feature = "profiling")]
+#[derive(Args, Clone, Debug)]
+pub struct ProfileArgs {
+    /// Write an HTML flamegraph of the run
+    #[arg(long, global = true, value_name = "FILE")]
+    pub flamegraph_html: Option<PathBuf>,
+    /// Write the recorded spans as JSON
+    #[arg(long, global = true, value_name = "FILE")]
+    pub flamegraph_json: Option<PathBuf>,
+    /// Print time and call counts per phase to stderr
+    #[arg(long, global = true)]
+    pub phases: bool,
+    /// Write time and call counts per phase as JSON
+    #[arg(long, global = true, value_name = "FILE")]
+    pub phases_json: Option<PathBuf>,
+}
+
+#[cfg(feature = "profiling")]
+impl ProfileArgs {
+    pub fn dump(&self) -> Result<(), String> {
+        if let Some(path) = &self.flamegraph_html {
+            let file: fs::File =
+                fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            flame::dump_html(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if let Some(path) = &self.flamegraph_json {
+            let mut file: fs::File =
+                fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            flame::dump_json(&mut file).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if self.phases || self.phases_json.is_some() {
+            let report: PhaseReport = PhaseReport::collect();
+            if self.phases {
+                eprintln!("{}", report);
+            }
+            if let Some(path) = &self.phases_json {
+                fs::write(path, report.to_json())
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Search for a regex consistent with the examples
+    Synth(SynthArgs),
+    /// Run the built-in example tasks, or time regex matching
+    ///
+    /// Unless a limit is given, each task times out after 10 seconds, as some built-in tasks
+    /// take far longer than that.
+    Bench(BenchArgs),
+    /// Print the cost of deriving a regex in the grammar
+    Cost(CostArgs),
+    /// Check a regex against the examples
+    Check(CheckArgs),
+}
+
+/// Examples come from a task file, repeated `-p`/`-n` arguments, files holding one example
+/// per line, or any mix of them. Every line of an example file counts, so a blank line is the
+/// empty string.
+#[derive(Args, Debug)]
+pub struct ExampleArgs {
+    /// Task file (TOML, JSON, or `+`/`-` lines) with examples and metadata
+    #[arg(short, long, value_name = "FILE")]
+    pub task: Option<PathBuf>,
+    /// A string the regex must accept
+    #[arg(short, long = "positive", value_name = "STRING")]
+    pub positive: Vec<String>,
+    /// A string the regex must reject
+    #[arg(short, long = "negative", value_name = "STRING")]
+    pub negative: Vec<String>,
+    /// File of strings the regex must accept, one per line
+    #[arg(long, value_name = "FILE")]
+    pub positive_file: Vec<PathBuf>,
+    /// File of strings the regex must reject, one per line
+    #[arg(long, value_name = "FILE")]
+    pub negative_file: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct GrammarArgs {
+    /// Symbols the regex may use, e.g. `01`; inferred when omitted
+    #[arg(short, long, value_name = "SYMBOLS")]
+    pub alphabet: Option<String>,
+    /// Grammar file (TOML, or JSON for `.json` files)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["costs", "plus", "optional", "wildcard", "class"])]
+    pub grammar: Option<PathBuf>,
+    /// Cost model for the built-in grammar (TOML)
+    #[arg(long, value_name = "FILE")]
+    pub costs: Option<PathBuf>,
+    /// Add `(x)+` to the built-in grammar
+    #[arg(long)]
+    pub plus: bool,
+    /// Add `(x)?` to the built-in grammar
+    #[arg(long)]
+    pub optional: bool,
+    /// Add `.` to the built-in grammar
+    #[arg(long)]
+    pub wildcard: bool,
+    /// Add a character class to the built-in grammar, e.g. `--class 01` for `[01]`
+    #[arg(long, value_name = "SYMBOLS")]
+    pub class: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LimitArgs {
+    /// Give up once every candidate up to this cost has been tried
+    #[arg(long, value_name = "COST")]
+    pub max_cost: Option<usize>,
+    /// Give up after this many seconds
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub timeout: Option<Duration>,
+    /// Give up after taking this many states off the queue
+    #[arg(long, value_name = "STATES")]
+    pub max_states: Option<usize>,
+    /// Give up once the states queued or still in the batch, and the seen-regex and signature
+    /// tables hold this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_memory: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Skip candidates that can no longer become consistent or are redundant
+    #[arg(long)]
+    pub pruning: bool,
+    /// Expand candidates by cost plus a lower bound on filling their holes (A*)
+    #[arg(long)]
+    pub astar: bool,
+    /// Check candidates on this many worker threads, 0 for one per core; the result is the same
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+    /// Finish the cheapest cost level and report every consistent regex of that cost
+    #[arg(long, conflicts_with = "top")]
+    pub all_minimal: bool,
+    /// Report the K cheapest consistent regexes, whatever their cost
+    #[arg(long, value_name = "K")]
+    pub top: Option<NonZeroUsize>,
+    /// Report only one regex per language
+    #[arg(long)]
+    pub distinct_languages: bool,
+    /// Print every pruned candidate
+    #[arg(long)]
+    pub debug: bool,
+    /// Print only the regex, without progress or counters
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// How to print results; `json` prints one report per run and no progress
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl SearchArgs {
+    fn verbose(&self) -> bool {
+        !self.quiet && self.format == Format::Text
+    }
+
+    fn options(&self) -> SearchOptions {
+        SearchOptions {
+            pruning: self.pruning,
+            debug: self.debug,
+            verbose: self.verbose(),
+            astar: self.astar,
+            threads: self.threads,
+            solutions: match self.top {
+                Some(k) => Solutions::Cheapest(k),
+                None if self.all_minimal => Solutions::AllMinimal,
+                None => Solutions::First,
+            },
+            distinct_languages: self.distinct_languages,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SynthArgs {
+    #[command(flatten)]
+    pub examples: ExampleArgs,
+    #[command(flatten)]
+    pub grammar: GrammarArgs,
+    #[command(flatten)]
+    pub limits: LimitArgs,
+    #[command(flatten)]
+    pub search: SearchArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Built-in task names or task files to run, every built-in task when omitted
+    #[arg(value_name = "TASK")]
+    pub tasks: Vec<String>,
+    /// Time matching the final regexes against random strings instead
+    #[arg(long)]
+    pub matching: bool,
+    /// Number of random strings for `--matching`
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10000000,
+        requires = "matching"
+    )]
+    pub set_len: usize,
+    /// Seed for the random strings of `--matching`; the same seed gives the same strings
+    #[arg(long, value_name = "SEED", default_value_t = 0, requires = "matching")]
+    pub seed: u64,
+    #[command(flatten)]
+    pub limits: LimitArgs,
+    #[command(flatten)]
+    pub search: SearchArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct CostArgs {
+    /// The regex, with or without `^`/`$` anchors
+    pub regex: String,
+    #[command(flatten)]
+    pub grammar: GrammarArgs,
+    /// Give up after this many derivations, for a grammar file with compound productions
+    #[arg(long, value_name = "STATES", default_value_t = COST_MAX_STATES)]
+    pub max_states: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// The regex, used as given, so anchor it to match whole strings; defaults to the task's
+    /// expected regex
+    pub regex: Option<String>,
+    #[command(flatten)]
+    pub examples: ExampleArgs,
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs: f64 = arg
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", arg))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, String> {
+    let text: String =
+        fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(text.lines().map(|x: &str| x.to_string()).collect())
+}
+
+fn load_task(path: &Path) -> Result<TestCase, String> {
+    TestCase::load(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl ExampleArgs {
+    /// The task file's examples followed by the arguments and then the example files, in
+    /// order. Symbols in the extra examples are added to the task's alphabet.
+    pub fn load(&self) -> Result<TestCase, String> {
+        let mut case: TestCase = match &self.task {
+            Some(path) => load_task(path)?,
+            None => TestCase::new(Vec::new(), Vec::new()),
+        };
+
+        let mut positive_set: Vec<String> = self.positive.clone();
+        for path in &self.positive_file {
+            positive_set.extend(read_lines(path)?);
+        }
+        let mut negative_set: Vec<String> = self.negative.clone();
+        for path in &self.negative_file {
+            negative_set.extend(read_lines(path)?);
+        }
+
+        let mut alphabet: BTreeSet<char> = case.alphabet.iter().copied().collect();
+        alphabet.extend(utils::infer_alphabet(&positive_set, &negative_set));
+        case.alphabet = alphabet.into_iter().collect();
+        case.positive_set.extend(positive_set);
+        case.negative_set.extend(negative_set);
+        Ok(case)
+    }
+}
+
+impl GrammarArgs {
+    pub fn load(&self) -> Result<Grammar, String> {
+        if let Some(path) = &self.grammar {
+            return Grammar::load(path).map_err(|e| format!("{}: {}", path.display(), e));
+        }
+        let costs: CostModel = match &self.costs {
+            Some(path) => {
+                CostModel::load(path).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => CostModel::default(),
+        };
+        let extra: ExtraProductions = ExtraProductions {
+            plus: self.plus,
+            optional: self.optional,
+            wildcard: self.wildcard,
+            classes: self
+                .class
+                .iter()
+                .map(|x: &String| x.chars().collect())
+                .collect(),
+        };
+        Grammar::new(&extra, &costs)
+    }
+}
+
+impl LimitArgs {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_cost: self.max_cost,
+            timeout: self.timeout,
+            max_states: self.max_states,
+            max_memory: self.max_memory,
+        }
+    }
+}
+
+impl BenchArgs {
+    /// The limits given, or a timeout of `BENCH_TIMEOUT` when there are none.
+    pub fn limits(&self) -> Limits {
+        let mut limits: Limits = self.limits.limits();
+        if limits == Limits::default() {
+            limits.timeout = Some(BENCH_TIMEOUT);
+        }
+        limits
+    }
+}
+
+impl SynthArgs {
+    /// The task described by the arguments, with any files read.
+    pub fn test_case(&self) -> Result<TestCase, String> {
+        let mut case: TestCase = self.examples.load()?;
+        if let Some(alphabet) = &self.grammar.alphabet {
+            case.alphabet = alphabet.chars().collect();
+        }
+        case.grammar = self.grammar.load()?;
+        case.limits = self.limits.limits();
+        case.verbose = self.search.verbose();
+        Ok(case)
+    }
+}
+
+/// Runs `cli`, reporting problems on stderr. The exit code is 1 when no consistent regex
+/// was found, the regex found is not the task's expected one, or the checked regex is
+/// inconsistent, and 2 for unusable input.
+#[cfg_attr(feature = "profiling", flamer::flame)]
+pub fn run(cli: Cli) -> ExitCode {
+    let result: Result<bool, String> = match cli.command {
+        Command::Synth(args) => synth(&args),
+        Command::Bench(args) => bench(&args),
+        Command::Cost(args) => cost(&args),
+        Command::Check(args) => check(&args),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn report(case: &TestCase, result: &Result<SynthOutcome, SynthError>, quiet: bool) -> bool {
+    match result {
+        Ok(outcome) => {
+            if quiet {
+                for regex in outcome.regexes() {
+                    println!("{}", regex);
+                }
+            }
+            match &case.expected {
+                Some(expected) if *expected != outcome.regex => {
+                    eprintln!("expected {}", expected);
+                    false
+                }
+                _ => true,
+            }
+        }
+        Err(e) => {
+            if quiet {
+                eprintln!("{}", e);
+            }
+            false
+        }
+    }
+}
+
+fn synth_report(
+    case: &TestCase,
+    search: &SearchArgs,
+) -> (SynthReport, Result<SynthOutcome, SynthError>) {
+    let start: Instant = Instant::now();
+    let options: SearchOptions = search.options();
+    let result: Result<SynthOutcome, SynthError> = case.synth(&options);
+    let report: SynthReport = SynthReport::new(case, &result, start.elapsed(), &options);
+    (report, result)
+}
+
+fn synth(args: &SynthArgs) -> Result<bool, String> {
+    let case: TestCase = args.test_case()?;
+    match args.search.format {
+        Format::Text => {
+            utils::validate_examples(&case.positive_set, &case.negative_set, &case.alphabet)
+                .map_err(|e| e.to_string())?;
+            let result: Result<SynthOutcome, SynthError> = case.synth(&args.search.options());
+            Ok(report(&case, &result, args.search.quiet))
+        }
+        Format::Json => {
+            let (report, result): (SynthReport, Result<SynthOutcome, SynthError>) =
+                synth_report(&case, &args.search);
+            println!("{}", report.to_json());
+            match result {
+                Err(e) if e.is_input_error() => Err(e.to_string()),
+                _ => Ok(report.succeeded()),
+            }
+        }
+    }
+}
+
+fn bench(args: &BenchArgs) -> Result<bool, String> {
+    if args.matching {
+        regex_bencharking::regex_benchmarking(args.set_len, args.seed);
+        return Ok(true);
+    }
+
+    let builtin: Vec<(&str, TestCase)> = cases::builtin();
+    let mut cases: Vec<(String, TestCase)> = Vec::new();
+    if args.tasks.is_empty() {
+        cases = builtin
+            .iter()
+            .map(|(name, case)| (name.to_string(), case.clone()))
+            .collect();
+    }
+    for task in &args.tasks {
+        if let Some((name, case)) = builtin.iter().find(|(name, _)| name == task) {
+            cases.push((name.to_string(), case.clone()));
+        } else if Path::new(task).is_file() {
+            let case: TestCase = load_task(Path::new(task))?;
+            cases.push((case.name.clone().unwrap_or_else(|| task.clone()), case));
+        } else {
+            let names: Vec<&str> = builtin.iter().map(|(name, _)| *name).collect();
+            return Err(format!(
+                "'{}' is neither a task file nor one of {}",
+                task,
+                names.join(", ")
+            ));
+        }
+    }
+
+    let mut all_found: bool = true;
+    let mut reports: Vec<SynthReport> = Vec::new();
+    for (name, mut case) in cases {
+        case.name = Some(name);
+        case.limits = args.limits();
+        case.verbose = args.search.verbose();
+        match args.search.format {
+            Format::Text => {
+                println!("{}", case.name.as_ref().unwrap());
+                let result: Result<SynthOutcome, SynthError> = case.synth(&args.search.options());
+                all_found &= report(&case, &result, args.search.quiet);
+            }
+            Format::Json => {
+                let (report, _): (SynthReport, Result<SynthOutcome, SynthError>) =
+                    synth_report(&case, &args.search);
+                all_found &= report.succeeded();
+                reports.push(report);
+            }
+        }
+    }
+    if args.search.format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    }
+    Ok(all_found)
+}
+
+fn collect_literals(regexp: &Node, symbols: &mut BTreeSet<char>) {
+    match regexp {
+        Node::Literal(c) => {
+            symbols.insert(*c);
+        }
+        Node::Hole | Node::Any | Node::Class(_) => {}
+        Node::Concat(left, right) => {
+            collect_literals(left, symbols);
+            collect_literals(right, symbols);
+        }
+        Node::Alt(branches) => {
+            for branch in branches {
+                collect_literals(branch, symbols);
+            }
+        }
+        Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => {
+            collect_literals(inner, symbols)
+        }
+    }
+}
+
+fn cost(args: &CostArgs) -> Result<bool, String> {
+    let regexp: Node = Node::parse(&args.regex)?;
+    let alphabet: Vec<char> = match &args.grammar.alphabet {
+        Some(alphabet) => alphabet.chars().collect(),
+        None => {
+            let mut symbols: BTreeSet<char> = BTreeSet::new();
+            collect_literals(&regexp, &mut symbols);
+            symbols.into_iter().collect()
+        }
+    };
+    let grammar: Grammar = args.grammar.load()?;
+
+    match utils::get_cost(&regexp, &alphabet, &grammar, Some(args.max_states)) {
+        Ok(Some(cost)) => {
+            println!("{}", cost);
+            Ok(true)
+        }
+        Ok(None) => {
+            eprintln!("{} cannot be derived in this grammar", regexp);
+            Ok(false)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            Ok(false)
+        }
+    }
+}
+
+fn check(args: &CheckArgs) -> Result<bool, String> {
+    let case: TestCase = args.examples.load()?;
+    let regexp: &String = args
+        .regex
+        .as_ref()
+        .or(case.expected.as_ref())
+        .ok_or_else(|| "no regex given and the task has no expected regex".to_string())?;
+    let re: Regex = Regex::new(regexp).map_err(|e| e.to_string())?;
+    let (positive_set, negative_set): (&Vec<String>, &Vec<String>) =
+        (&case.positive_set, &case.negative_set);
+
+    let mut consistent: bool = true;
+    for x in positive_set.iter().filter(|x: &&String| !re.is_match(x)) {
+        println!("rejects positive {:?}", x);
+        consistent = false;
+    }
+    for x in negative_set.iter().filter(|x: &&String| re.is_match(x)) {
+        println!("accepts negative {:?}", x);
+        consistent = false;
+    }
+    if consistent {
+        println!(
+            "consistent with {} positive and {} negative examples",
+            positive_set.len(),
+            negative_set.len()
+        );
+    }
+    Ok(consistent)
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Cost of each production kind. The search returns the cheapest consistent regex, so raising
+/// a cost (e.g. `alt`) steers it toward regexes that avoid that construct.
+///
+/// Cost models are written as TOML; omitted kinds keep their default cost of 1:
+///
+/// ```toml
+/// literal = 1
+/// alt = 3
+/// wildcard = 2
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostModel {
+    pub literal: usize,
+    pub star: usize,
+    pub concat: usize,
+    pub alt: usize,
+    pub plus: usize,
+    pub optional: usize,
+    pub wildcard: usize,
+    pub class: usize,
+}
+
+impl Default for CostModel {
+    fn default() -> CostModel {
+        CostModel {
+            literal: 1,
+            star: 1,
+            concat: 1,
+            alt: 1,
+            plus: 1,
+            optional: 1,
+            wildcard: 1,
+            class: 1,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn from_toml(text: &str) -> Result<CostModel, String> {
+        let costs: CostModel = toml::from_str(text).map_err(|e| e.to_string())?;
+        costs.validate()?;
+        Ok(costs)
+    }
+
+    pub fn load(path: &Path) -> Result<CostModel, String> {
+        let text: String = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        CostModel::from_toml(&text)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+
+    fn all(&self) -> [usize; 8] {
+        [
+            self.literal,
+            self.star,
+            self.concat,
+            self.alt,
+            self.plus,
+            self.optional,
+            self.wildcard,
+            self.class,
+        ]
+    }
+
+    /// Rejects free productions, which could be applied forever without leaving their cost
+    /// level.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.all().contains(&0) {
+            return Err("every production must cost at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::Node;
+    use crate::grammar::{ExtraProductions, Grammar};
+    use crate::utils::{self, State};
+
+    #[test]
+    fn cost_model_steers_the_result() {
+        let ps: Vec<String> = ["0", "1"].iter().map(|&x| x.to_string()).collect();
+        let ns: Vec<String> = ["", "01"].iter().map(|&x| x.to_string()).collect();
+        let extra: ExtraProductions = ExtraProductions {
+            classes: vec![vec!['0', '1']],
+            ..Default::default()
+        };
+        let cheap_alt: Grammar =
+            Grammar::new(&extra, &CostModel::from_toml("class = 5").unwrap()).unwrap();
+        let cheap_class: Grammar =
+            Grammar::new(&extra, &CostModel::from_toml("alt = 5").unwrap()).unwrap();
+
+        let state: State = utils::synth(
+            &ps,
+            &ns,
+            &['0', '1'],
+            &cheap_alt,
+            &utils::Limits::default(),
+            &utils::SearchOptions::default(),
+        )
+        .unwrap()
+        .state;
+        assert_eq!(state.regexp.to_string(), "^(0|1)$");
+        assert_eq!(state.cost, 3);
+        let state: State = utils::synth(
+            &ps,
+            &ns,
+            &['0', '1'],
+            &cheap_class,
+            &utils::Limits::default(),
+            &utils::SearchOptions::default(),
+        )
+        .unwrap()
+        .state;
+        assert_eq!(state.regexp.to_string(), "^[01]$");
+        assert_eq!(
+            utils::get_cost(
+                &Node::parse("^(0|1)$").unwrap(),
+                &['0', '1'],
+                &cheap_class,
+                None
+            ),
+            Ok(Some(7))
+        );
+        assert!(CostModel::from_toml("star = 0").is_err());
+    }
+}
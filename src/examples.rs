@@ -0,0 +1,282 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::ops::Range;
+
+/// How the lengths of generated strings are drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LengthDistribution {
+    /// every length in the range equally likely
+    Uniform(Range<usize>),
+    /// length `i` with probability proportional to `weights[i]`
+    Weighted(Vec<f64>),
+}
+
+impl LengthDistribution {
+    /// The lengths that can be drawn.
+    fn support(&self) -> Vec<usize> {
+        match self {
+            LengthDistribution::Uniform(range) => range.clone().collect(),
+            LengthDistribution::Weighted(weights) => (0..weights.len())
+                .filter(|i: &usize| weights[*i] > 0.0)
+                .collect(),
+        }
+    }
+}
+
+/// Why `ExampleGenerator` could not produce the requested examples.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GenerationError {
+    /// the length distribution is empty or its weights are unusable
+    InvalidLengths(String),
+    /// fewer strings than requested exist at the lengths that can be drawn
+    TooFewStrings { requested: usize, available: usize },
+    /// this many draws in a row found nothing new, after collecting `found` examples
+    GaveUp {
+        requested: usize,
+        found: usize,
+        misses: usize,
+    },
+}
+
+impl Display for GenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerationError::InvalidLengths(message) => {
+                write!(f, "invalid length distribution: {}", message)
+            }
+            GenerationError::TooFewStrings {
+                requested,
+                available,
+            } => write!(
+                f,
+                "asked for {} examples but only {} strings have a possible length",
+                requested, available
+            ),
+            GenerationError::GaveUp {
+                requested,
+                found,
+                misses,
+            } => write!(
+                f,
+                "found {} of {} examples, then {} draws in a row found nothing new",
+                found, requested, misses
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Draws distinct random strings over an alphabet, such as negative examples for a reference
+/// language.
+///
+/// ```
+/// use regexsynth::examples::ExampleGenerator;
+///
+/// let negatives = ExampleGenerator::new(['0', '1'])
+///     .seed(7)
+///     .negatives(|x: &str| x.starts_with('0'), 10)
+///     .unwrap();
+/// assert_eq!(negatives.len(), 10);
+/// assert!(negatives.iter().all(|x| !x.starts_with('0')));
+/// ```
+///
+/// Lengths are uniform over 1 to 49 unless set. The same seed gives the same examples in the
+/// same order on any machine, as ChaCha8's output is fixed by its algorithm.
+#[derive(Clone, Debug)]
+pub struct ExampleGenerator {
+    alphabet: Vec<char>,
+    lengths: LengthDistribution,
+    seed: u64,
+    max_misses: usize,
+}
+
+impl ExampleGenerator {
+    pub fn new<I: IntoIterator<Item = char>>(alphabet: I) -> ExampleGenerator {
+        ExampleGenerator {
+            alphabet: alphabet.into_iter().collect(),
+            lengths: LengthDistribution::Uniform(1..50),
+            seed: 0,
+            max_misses: 100_000,
+        }
+    }
+
+    pub fn lengths(mut self, lengths: LengthDistribution) -> ExampleGenerator {
+        self.lengths = lengths;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> ExampleGenerator {
+        self.seed = seed;
+        self
+    }
+
+    /// Give up after this many draws in a row that are rejected or already taken.
+    pub fn max_misses(mut self, max_misses: usize) -> ExampleGenerator {
+        self.max_misses = max_misses;
+        self
+    }
+
+    /// Number of strings over the alphabet at the lengths that can be drawn, saturating.
+    fn available(&self, support: &[usize]) -> usize {
+        support
+            .iter()
+            .map(|len: &usize| {
+                u32::try_from(*len)
+                    .ok()
+                    .and_then(|len: u32| self.alphabet.len().checked_pow(len))
+                    .unwrap_or(usize::MAX)
+            })
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Draws `count` distinct strings that `keep` accepts, in the order they were drawn.
+    pub fn generate<P: Fn(&str) -> bool>(
+        &self,
+        keep: P,
+        count: usize,
+    ) -> Result<Vec<String>, GenerationError> {
+        let mut support: Vec<usize> = self.lengths.support();
+        // without symbols only the empty string exists
+        support.retain(|len: &usize| *len == 0 || !self.alphabet.is_empty());
+        if support.is_empty() {
+            return Err(GenerationError::InvalidLengths(
+                "no length can be drawn".to_string(),
+            ));
+        }
+        let available: usize = self.available(&support);
+        if count > available {
+            return Err(GenerationError::TooFewStrings {
+                requested: count,
+                available,
+            });
+        }
+        let weighted: Option<WeightedIndex<f64>> = match &self.lengths {
+            LengthDistribution::Uniform(_) => None,
+            LengthDistribution::Weighted(weights) => {
+                let weights: Vec<f64> = (0..weights.len())
+                    .map(|i: usize| {
+                        if support.contains(&i) {
+                            weights[i]
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                Some(
+                    WeightedIndex::new(weights)
+                        .map_err(|e| GenerationError::InvalidLengths(e.to_string()))?,
+                )
+            }
+        };
+
+        let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut examples: Vec<String> = Vec::new();
+        let mut misses: usize = 0;
+        while examples.len() < count {
+            let len: usize = match &weighted {
+                Some(weighted) => weighted.sample(&mut rng),
+                None => support[rng.gen_range(0..support.len())],
+            };
+            let x: String = (0..len)
+                .map(|_| self.alphabet[rng.gen_range(0..self.alphabet.len())])
+                .collect();
+            if seen.contains(&x) || !keep(&x) {
+                misses += 1;
+                if misses >= self.max_misses {
+                    return Err(GenerationError::GaveUp {
+                        requested: count,
+                        found: examples.len(),
+                        misses,
+                    });
+                }
+                continue;
+            }
+            misses = 0;
+            seen.insert(x.clone());
+            examples.push(x);
+        }
+
+        Ok(examples)
+    }
+
+    /// Draws `count` distinct strings outside the language that `accepts` describes.
+    pub fn negatives<P: Fn(&str) -> bool>(
+        &self,
+        accepts: P,
+        count: usize,
+    ) -> Result<Vec<String>, GenerationError> {
+        self.generate(|x: &str| !accepts(x), count)
+    }
+
+    /// Draws `count` distinct strings that `regex` does not match; anchor it, as the
+    /// synthesized regexes are, to reject strings rather than substrings.
+    pub fn negatives_of_regex(
+        &self,
+        regex: &Regex,
+        count: usize,
+    ) -> Result<Vec<String>, GenerationError> {
+        self.negatives(|x: &str| regex.is_match(x), count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn negative_examples_come_from_any_predicate() {
+        let re: regex::Regex = regex::Regex::new("^0(0|1)*$").unwrap();
+        let negatives: Vec<String> = ExampleGenerator::new(['0', '1'])
+            .lengths(LengthDistribution::Weighted(vec![0.0, 1.0, 1.0, 2.0, 4.0]))
+            .negatives_of_regex(&re, 12)
+            .unwrap();
+        assert_eq!(negatives.len(), 12);
+        assert!(negatives
+            .iter()
+            .all(|x: &String| x.starts_with('1') && (1..5).contains(&x.len())));
+        assert_eq!(
+            negatives.iter().collect::<HashSet<&String>>().len(),
+            negatives.len()
+        );
+
+        // only "" and "00" are not "0" at lengths 0 to 2 over one symbol
+        let narrow: ExampleGenerator =
+            ExampleGenerator::new(['0']).lengths(LengthDistribution::Uniform(0..3));
+        assert_eq!(narrow.negatives(|x: &str| x == "0", 2).unwrap().len(), 2);
+        assert_eq!(
+            narrow.negatives(|x: &str| x == "0", 4),
+            Err(GenerationError::TooFewStrings {
+                requested: 4,
+                available: 3
+            })
+        );
+        assert!(matches!(
+            narrow.max_misses(50).negatives(|x: &str| x == "0", 3),
+            Err(GenerationError::GaveUp {
+                requested: 3,
+                found: 2,
+                misses: 50
+            })
+        ));
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_examples() {
+        let negatives = |seed: u64| {
+            ExampleGenerator::new(['0', '1'])
+                .lengths(LengthDistribution::Uniform(0..6))
+                .seed(seed)
+                .negatives(|x: &str| x.ends_with("01"), 6)
+                .unwrap()
+        };
+        assert_eq!(negatives(7), ["1", "", "0", "1100", "0011", "10111"]);
+        assert_eq!(negatives(8), ["", "1110", "110", "111", "0", "000"]);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::ast::{Node, HOLE};
+use crate::cost::CostModel;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Productions that can be enabled on top of literals, `(\x00)*`, `\x00\x00` and `(\x00|\x00)`.
+#[derive(Clone, Debug, Default)]
+pub struct ExtraProductions {
+    /// `(\x00)+`
+    pub plus: bool,
+    /// `(\x00)?`
+    pub optional: bool,
+    /// `.`
+    pub wildcard: bool,
+    /// one terminal per class, e.g. `[01]`
+    pub classes: Vec<Vec<char>>,
+}
+
+/// A production that replaces the leftmost hole with `pattern`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Production {
+    pub pattern: Node,
+    pub cost: usize,
+}
+
+/// The synthesis grammar: one terminal per alphabet symbol plus a list of productions, tried
+/// in order. A production introduces a group exactly when its pattern is parenthesised, and an
+/// alternation filling a branch of another alternation is spliced into it.
+///
+/// Grammars are written as TOML (or JSON, for `.json` files):
+///
+/// ```toml
+/// literal_cost = 1
+///
+/// [[production]]
+/// pattern = '(\x00)*'
+/// cost = 1
+///
+/// [[production]]
+/// pattern = '(\x00|\x00)'
+/// cost = 3
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grammar {
+    /// cost of each alphabet terminal, `None` when the grammar has no literal productions
+    pub literal_cost: Option<usize>,
+    pub productions: Vec<Production>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GrammarFile {
+    #[serde(default)]
+    literal_cost: Option<usize>,
+    #[serde(default, rename = "production", alias = "productions")]
+    productions: Vec<ProductionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProductionEntry {
+    pattern: String,
+    cost: usize,
+}
+
+impl Default for Grammar {
+    fn default() -> Grammar {
+        Grammar::new(&ExtraProductions::default(), &CostModel::default()).unwrap()
+    }
+}
+
+impl Grammar {
+    /// The built-in grammar: `(\x00)*`, `\x00\x00`, `(\x00|\x00)` and whichever `extra`
+    /// productions are enabled, each priced by `costs`, which must not make any of them free.
+    pub fn new(extra: &ExtraProductions, costs: &CostModel) -> Result<Grammar, String> {
+        costs.validate()?;
+        let mut productions: Vec<Production> = vec![
+            Production {
+                pattern: Node::star(Node::Hole),
+                cost: costs.star,
+            },
+            Production {
+                pattern: Node::concat(Node::Hole, Node::Hole),
+                cost: costs.concat,
+            },
+            Production {
+                pattern: Node::alt(Node::Hole, Node::Hole),
+                cost: costs.alt,
+            },
+        ];
+
+        if extra.plus {
+            productions.push(Production {
+                pattern: Node::plus(Node::Hole),
+                cost: costs.plus,
+            });
+        }
+        if extra.optional {
+            productions.push(Production {
+                pattern: Node::optional(Node::Hole),
+                cost: costs.optional,
+            });
+        }
+        if extra.wildcard {
+            productions.push(Production {
+                pattern: Node::Any,
+                cost: costs.wildcard,
+            });
+        }
+        for class in &extra.classes {
+            productions.push(Production {
+                pattern: Node::Class(class.clone()),
+                cost: costs.class,
+            });
+        }
+
+        Ok(Grammar {
+            literal_cost: Some(costs.literal),
+            productions,
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Grammar, String> {
+        let file: GrammarFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        Grammar::from_file(file)
+    }
+
+    pub fn from_json(text: &str) -> Result<Grammar, String> {
+        let file: GrammarFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        Grammar::from_file(file)
+    }
+
+    /// Loads a grammar, reading `.json` files as JSON and anything else as TOML.
+    pub fn load(path: &Path) -> Result<Grammar, String> {
+        let text: String = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Grammar::from_json(&text)
+        } else {
+            Grammar::from_toml(&text)
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.to_file()).unwrap()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_file()).unwrap()
+    }
+
+    /// Rejects free productions and productions that only reproduce a hole, neither of which
+    /// ever makes progress; the search relies on every substitution costing at least 1.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.literal_cost == Some(0) {
+            return Err("literal_cost must be at least 1".to_string());
+        }
+        for p in &self.productions {
+            if p.cost == 0 {
+                return Err(format!(
+                    "production '{}' must cost at least 1",
+                    p.pattern.render(HOLE)
+                ));
+            }
+            if p.pattern == Node::Hole {
+                return Err(format!("production '{}' is a bare hole", HOLE));
+            }
+        }
+        Ok(())
+    }
+
+    fn from_file(file: GrammarFile) -> Result<Grammar, String> {
+        let mut productions: Vec<Production> = Vec::new();
+        for entry in file.productions {
+            let pattern: Node = Node::parse(&entry.pattern)
+                .map_err(|e| format!("production '{}': {}", entry.pattern, e))?;
+            productions.push(Production {
+                pattern,
+                cost: entry.cost,
+            });
+        }
+
+        let grammar: Grammar = Grammar {
+            literal_cost: file.literal_cost,
+            productions,
+        };
+        grammar.validate()?;
+        Ok(grammar)
+    }
+
+    fn to_file(&self) -> GrammarFile {
+        GrammarFile {
+            literal_cost: self.literal_cost,
+            productions: self
+                .productions
+                .iter()
+                .map(|p: &Production| ProductionEntry {
+                    pattern: p.pattern.render(HOLE),
+                    cost: p.cost,
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether every production is a terminal or a single operator over holes, with two-way
+    /// alternations, as in the built-in grammar. Only then can any regex be rewritten into its
+    /// canonical form (see `Node::is_canonical`) without leaving the grammar or raising its cost.
+    pub fn is_elementary(&self) -> bool {
+        self.productions
+            .iter()
+            .all(|p: &Production| match &p.pattern {
+                Node::Hole => false,
+                Node::Literal(_) | Node::Any | Node::Class(_) => true,
+                Node::Concat(left, right) => **left == Node::Hole && **right == Node::Hole,
+                Node::Alt(branches) => *branches == [Node::Hole, Node::Hole],
+                Node::Star(inner) | Node::Plus(inner) | Node::Optional(inner) => {
+                    **inner == Node::Hole
+                }
+            })
+    }
+
+    /// The cost of deriving `regexp` with one production per node, `None` when a node has no
+    /// production. This is the cheapest derivation only when the grammar `is_elementary`.
+    pub fn derivation_cost(&self, regexp: &Node, alphabet: &[char]) -> Option<usize> {
+        let cheapest = |pattern: &Node| -> Option<usize> {
+            self.productions
+                .iter()
+                .filter(|p: &&Production| p.pattern == *pattern)
+                .map(|p: &Production| p.cost)
+                .min()
+        };
+        match regexp {
+            Node::Hole => None,
+            Node::Literal(c) => {
+                let terminal: Option<usize> = self.literal_cost.filter(|_| alphabet.contains(c));
+                match (terminal, cheapest(regexp)) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                }
+            }
+            Node::Any | Node::Class(_) => cheapest(regexp),
+            Node::Concat(left, right) => Some(
+                cheapest(&Node::concat(Node::Hole, Node::Hole))?
+                    + self.derivation_cost(left, alphabet)?
+                    + self.derivation_cost(right, alphabet)?,
+            ),
+            // an alternation of n branches splices n - 1 two-way ones
+            Node::Alt(branches) => {
+                let mut cost: usize = cheapest(&Node::alt(Node::Hole, Node::Hole))?
+                    * branches.len().saturating_sub(1);
+                for branch in branches {
+                    cost += self.derivation_cost(branch, alphabet)?;
+                }
+                Some(cost)
+            }
+            Node::Star(inner) => {
+                Some(cheapest(&Node::star(Node::Hole))? + self.derivation_cost(inner, alphabet)?)
+            }
+            Node::Plus(inner) => {
+                Some(cheapest(&Node::plus(Node::Hole))? + self.derivation_cost(inner, alphabet)?)
+            }
+            Node::Optional(inner) => Some(
+                cheapest(&Node::optional(Node::Hole))? + self.derivation_cost(inner, alphabet)?,
+            ),
+        }
+    }
+
+    /// The most expensive production, used to scale the queue's cost ceiling.
+    pub fn max_cost(&self) -> usize {
+        self.productions
+            .iter()
+            .map(|p: &Production| p.cost)
+            .chain(self.literal_cost)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Builds the substitution table for `alphabet` as (substitute, cost) pairs: one terminal
+    /// per symbol (escaped when rendered), followed by the productions in order.
+    pub fn substitutions(&self, alphabet: &[char]) -> Vec<(Node, usize)> {
+        let mut subs: Vec<(Node, usize)> = Vec::new();
+
+        if let Some(cost) = self.literal_cost {
+            for c in alphabet {
+                subs.push((Node::Literal(*c), cost));
+            }
+        }
+        for p in &self.productions {
+            subs.push((p.pattern.clone(), p.cost));
+        }
+
+        subs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::outcome::SynthError;
+    use crate::utils::{self, State};
+
+    #[test]
+    fn grammar_file_round_trips() {
+        let toml: &str = r#"
+            literal_cost = 1
+
+            [[production]]
+            pattern = '(\x00)+'
+            cost = 1
+
+            [[production]]
+            pattern = '\x00\x00'
+            cost = 1
+        "#;
+        let g: Grammar = Grammar::from_toml(toml).unwrap();
+        assert_eq!(g.productions[0].pattern, Node::plus(Node::Hole));
+        assert_eq!(Grammar::from_json(&g.to_json()).unwrap(), g);
+        assert_eq!(Grammar::from_toml(&g.to_toml()).unwrap(), g);
+        assert!(Grammar::from_toml("[[production]]\npattern = '(0'\ncost = 1").is_err());
+
+        let ps: Vec<String> = ["10", "110"].iter().map(|&x| x.to_string()).collect();
+        let ns: Vec<String> = ["0", "1", "100"].iter().map(|&x| x.to_string()).collect();
+        let state: State = utils::synth(
+            &ps,
+            &ns,
+            &['0', '1'],
+            &g,
+            &utils::Limits::default(),
+            &utils::SearchOptions::default(),
+        )
+        .unwrap()
+        .state;
+        assert_eq!(state.regexp.to_string(), "^(1)+0$");
+    }
+
+    #[test]
+    fn elementary_grammars_are_priced_on_the_tree() {
+        let cost = |regexp: &str, g: &Grammar, max_states: Option<usize>| {
+            utils::get_cost(&Node::parse(regexp).unwrap(), &['0', '1'], g, max_states)
+        };
+        let g: Grammar = Grammar::default();
+        assert_eq!(cost("^(0|1)*0101(0|1)*$", &g, None), Ok(Some(17)));
+        assert_eq!(cost("(0)+", &g, None), Ok(None));
+        assert_eq!(cost("2", &g, None), Ok(None));
+
+        // `(0)*0` costs one production, so it must be searched for
+        let compound: Grammar = Grammar::from_toml(
+            "literal_cost = 1\n[[production]]\npattern = '(\\x00)*\\x00'\ncost = 1\n\
+             [[production]]\npattern = '(\\x00)*'\ncost = 5\n",
+        )
+        .unwrap();
+        assert_eq!(cost("(0)*0", &compound, None), Ok(Some(3)));
+        assert_eq!(cost("(0)*", &compound, None), Ok(Some(6)));
+        assert!(matches!(
+            cost("(1)*1(0)*0", &compound, Some(3)),
+            Err(SynthError::StateLimit(3, _))
+        ));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::ast::Node;
+use std::collections::HashSet;
+
+/// A regular expression normalised enough for its Brzozowski derivatives to be finitely many:
+/// alternations are flattened, sorted and deduplicated, and concatenations nest to the right.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum Re {
+    Empty,
+    Epsilon,
+    /// any one of these symbols, sorted and never empty
+    Symbols(Vec<char>),
+    Concat(Box<Re>, Box<Re>),
+    /// at least two sorted branches, none of them `Empty` or an `Alt`, and one `Symbols` at most
+    Alt(Vec<Re>),
+    Star(Box<Re>),
+}
+
+impl Re {
+    fn symbols(symbols: &[char]) -> Re {
+        let mut symbols: Vec<char> = symbols.to_vec();
+        symbols.sort();
+        symbols.dedup();
+        if symbols.is_empty() {
+            Re::Empty
+        } else {
+            Re::Symbols(symbols)
+        }
+    }
+
+    fn concat(left: Re, right: Re) -> Re {
+        match (left, right) {
+            (Re::Empty, _) | (_, Re::Empty) => Re::Empty,
+            (Re::Epsilon, other) | (other, Re::Epsilon) => other,
+            (Re::Concat(a, b), right) => Re::concat(*a, Re::concat(*b, right)),
+            (left, right) => Re::Concat(Box::new(left), Box::new(right)),
+        }
+    }
+
+    fn alt(branches: Vec<Re>) -> Re {
+        let mut merged: Vec<Re> = Vec::new();
+        let mut symbols: Vec<char> = Vec::new();
+        let mut todo: Vec<Re> = branches;
+        while let Some(branch) = todo.pop() {
+            match branch {
+                Re::Empty => {}
+                Re::Alt(inner) => todo.extend(inner),
+                Re::Symbols(s) => symbols.extend(s),
+                other => merged.push(other),
+            }
+        }
+        if !symbols.is_empty() {
+            merged.push(Re::symbols(&symbols));
+        }
+        merged.sort();
+        merged.dedup();
+        match merged.len() {
+            0 => Re::Empty,
+            1 => merged.pop().unwrap(),
+            _ => Re::Alt(merged),
+        }
+    }
+
+    fn star(inner: Re) -> Re {
+        match inner {
+            Re::Empty | Re::Epsilon => Re::Epsilon,
+            Re::Star(_) => inner,
+            other => Re::Star(Box::new(other)),
+        }
+    }
+
+    /// Translates a complete regex; `.` stands for any symbol of `alphabet` but a newline, as in
+    /// `regex`.
+    fn from_node(node: &Node, alphabet: &[char]) -> Re {
+        match node {
+            Node::Hole => panic!("cannot compare the language of a partial regex"),
+            Node::Literal(c) => Re::symbols(&[*c]),
+            Node::Any => {
+                let symbols: Vec<char> = alphabet
+                    .iter()
+                    .copied()
+                    .filter(|c: &char| *c != '\n')
+                    .collect();
+                Re::symbols(&symbols)
+            }
+            Node::Class(symbols) => Re::symbols(symbols),
+            Node::Concat(left, right) => Re::concat(
+                Re::from_node(left, alphabet),
+                Re::from_node(right, alphabet),
+            ),
+            Node::Alt(branches) => Re::alt(
+                branches
+                    .iter()
+                    .map(|b: &Node| Re::from_node(b, alphabet))
+                    .collect(),
+            ),
+            Node::Star(inner) => Re::star(Re::from_node(inner, alphabet)),
+            Node::Plus(inner) => {
+                let inner: Re = Re::from_node(inner, alphabet);
+                Re::concat(inner.clone(), Re::star(inner))
+            }
+            Node::Optional(inner) => Re::alt(vec![Re::Epsilon, Re::from_node(inner, alphabet)]),
+        }
+    }
+
+    fn nullable(&self) -> bool {
+        match self {
+            Re::Empty | Re::Symbols(_) => false,
+            Re::Epsilon | Re::Star(_) => true,
+            Re::Concat(left, right) => left.nullable() && right.nullable(),
+            Re::Alt(branches) => branches.iter().any(Re::nullable),
+        }
+    }
+
+    /// The strings `w` such that `c w` is in the language.
+    fn derivative(&self, c: char) -> Re {
+        match self {
+            Re::Empty | Re::Epsilon => Re::Empty,
+            Re::Symbols(symbols) => {
+                if symbols.contains(&c) {
+                    Re::Epsilon
+                } else {
+                    Re::Empty
+                }
+            }
+            Re::Concat(left, right) => {
+                let first: Re = Re::concat(left.derivative(c), (**right).clone());
+                if left.nullable() {
+                    Re::alt(vec![first, right.derivative(c)])
+                } else {
+                    first
+                }
+            }
+            Re::Alt(branches) => Re::alt(branches.iter().map(|b: &Re| b.derivative(c)).collect()),
+            Re::Star(inner) => Re::concat(inner.derivative(c), self.clone()),
+        }
+    }
+}
+
+/// Whether two complete regexes accept the same strings over `alphabet`, by checking that
+/// every pair of derivatives reachable from them agrees on accepting the empty string.
+pub fn equivalent(a: &Node, b: &Node, alphabet: &[char]) -> bool {
+    let mut seen: HashSet<(Re, Re)> = HashSet::new();
+    let mut todo: Vec<(Re, Re)> = vec![(Re::from_node(a, alphabet), Re::from_node(b, alphabet))];
+    while let Some((x, y)) = todo.pop() {
+        if x == y || seen.contains(&(x.clone(), y.clone())) {
+            continue;
+        }
+        if x.nullable() != y.nullable() {
+            return false;
+        }
+        for c in alphabet {
+            todo.push((x.derivative(*c), y.derivative(*c)));
+        }
+        seen.insert((x, y));
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equivalence_is_decided_by_derivatives() {
+        let node = |regexp: &str| Node::parse(regexp).unwrap();
+        let ab: [char; 2] = ['0', '1'];
+        assert!(equivalent(&node("(0|1)*"), &node("((0)*(1)*)*"), &ab));
+        assert!(equivalent(&node("0(0)*"), &node("(0)+"), &ab));
+        assert!(equivalent(&node("."), &node("[01]"), &ab));
+        assert!(!equivalent(&node("(0)*"), &node("(0)+"), &ab));
+        assert!(!equivalent(&node("(01)*"), &node("(10)*"), &ab));
+
+        let lines: [char; 2] = ['0', '\n'];
+        assert!(equivalent(&node("."), &node("0"), &lines));
+        assert!(!equivalent(&node("."), &node("[0\n]"), &lines));
+    }
+}
@@ -0,0 +1,27 @@
+//! Enumerative synthesis of regular expressions from positive and negative examples.
+//!
+//! Candidates are partial regexes whose holes are filled leftmost-first with the productions
+//! of a [`Grammar`], cheapest first, so the first complete regex that accepts every positive
+//! example and rejects every negative one is a cheapest consistent regex. Start from
+//! [`Synthesizer`].
+//!
+//! The search is deterministic: hash tables are only ever probed, never iterated, and parallel
+//! results are merged in queue order, so the same task always yields the same regex and
+//! counters. Random example generation takes an explicit seed.
+pub mod ast;
+pub mod cost;
+pub mod examples;
+pub mod grammar;
+pub mod language;
+pub mod outcome;
+#[cfg(feature = "profiling")]
+pub mod profile;
+pub mod synthesizer;
+pub mod task;
+pub mod utils;
+
+pub use cost::CostModel;
+pub use grammar::{ExtraProductions, Grammar};
+pub use outcome::{SynthError, SynthOutcome, SynthReport, SynthStats};
+pub use synthesizer::Synthesizer;
+pub use utils::{Limits, SearchOptions, Solutions, TestCase};
@@ -1,210 +1,94 @@
-#[allow(dead_code)]
+mod cases;
+mod cli;
 mod regex_bencharking;
-mod utils;
-use flame as f;
-use flamer::flame;
-use std::env;
-use std::fs::File;
-use std::time::{Duration, Instant};
-use utils::State;
-use utils::TestCase;
+use clap::Parser;
+use std::process::ExitCode;
 
-#[flame]
-fn main() {
-    // env::set_var("RUST_BACKTRACE", "1");
-    // let neg_set_len: usize = 1000;
-    let start_with_0: TestCase = TestCase::new(
-        vec!["01", "01101", "0001"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-        // utils::negative_examples("start_with_0", neg_set_len),
-        vec!["10", "1", "11010"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-    );
-    let end_with_01: TestCase = TestCase::new(
-        vec!["101", "001101101", "0110001"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-        // utils::negative_examples("end_with_01", neg_set_len),
-        vec!["100101011", "110000", "00111010"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-    );
-    let begin_with_1_and_end_with_0: TestCase = TestCase::new(
-        vec![
-            "11101001010010101000",
-            "100101001011101011100",
-            "10010111010010100010",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-        // utils::negative_examples("begin_1_end_0", neg_set_len),
-        vec![
-            "101001010010101000111",
-            "00010101010100100010110",
-            "00010101010101001011",
-            "0011010100110000001111010100",
-            "1001010010101011111111001011",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-    );
-    let containing_substring_0101: TestCase = TestCase::new(
-        vec!["0101", "00101001", "000101111"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-        // utils::negative_examples("containing_0101", neg_set_len),
-        vec!["10", "1", "11010", "1001100", "00100010", "0110110"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-    );
-    let have_at_most_two_0s: TestCase = TestCase::new(
-        vec![
-            "0101",
-            "1111011111011111111111111111111",
-            "111110111111111111011111111",
-            "00",
-            "011111111",
-            "11110",
-            "11111",
-            "1110111",
-            "1100",
-            "011",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-        vec![
-            "000",
-            "1101100101001010010",
-            "11010",
-            "1001100",
-            "00100010",
-            "0110110",
-            "000000010000",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-    );
-    let length_is_at_least_3_and_the_third_symbol_is_0: TestCase = TestCase::new(
-        vec!["110", "0100010100", "000111"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-        vec!["10", "101100", "0", "111000"]
-            .iter()
-            .map(|&x| x.to_string())
-            .collect(),
-    );
-    let each_0_is_followed_by_at_least_one_1: TestCase = TestCase::new(
-        vec![
-            "01",
-            "1010111011101011101011101",
-            "01011011101111011111",
-            "011010111",
-            "11010110101111",
-            "01111",
-            "1101",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-        vec![
-            "0000",
-            "01110001011",
-            "011010000",
-            "0110001",
-            "0001011010",
-            "00101100100",
-        ]
-        .iter()
-        .map(|&x| x.to_string())
-        .collect(),
-    );
+fn main() -> ExitCode {
+    let cli: cli::Cli = cli::Cli::parse();
+    #[cfg(feature = "profiling")]
+    let profile: cli::ProfileArgs = cli.profile.clone();
 
-    let cases: Vec<utils::TestCase> = vec![
-        start_with_0,
-        end_with_01, //^((1)*0)*1$
-        begin_with_1_and_end_with_0,
-        containing_substring_0101,
-        // &have_at_most_two_0s,
-        // &length_is_at_least_3_and_the_third_symbol_is_0,
-        // &each_0_is_followed_by_at_least_one_1,
-    ];
+    let code: ExitCode = cli::run(cli);
 
-    for c in &cases[1..2] {
-        c.synth(false, false);
+    #[cfg(feature = "profiling")]
+    if let Err(e) = profile.dump() {
+        eprintln!("error: {}", e);
+        return ExitCode::from(2);
     }
-    // println!("{}", utils::get_cost(r"^((0|1))*011$".to_string()));
-    // utils::is_redundant(&r"^(0\x00)*$".to_string(), &start_with_0.positive_set);
-    // f::dump_html(File::create("flamegraph2.html").unwrap()).unwrap();
-    // f::dump_json(&mut File::create("flamegraph.json").unwrap()).unwrap();
+    code
 }
 
 #[cfg(test)]
 mod test {
-    use std::collections::LinkedList;
-
     use super::*;
+    use regexsynth::utils::TestCase;
 
     #[test]
-    fn start_with_0() {
-        let ps: Vec<String> = vec!["01".to_string(), "01101".to_string(), "0001".to_string()];
-        let ns: Vec<String> = vec!["10".to_string(), "1".to_string(), "11010".to_string()];
-        let state: utils::State = utils::synth(&ps, &ns, false, false);
-        // assert_eq!(
-        //     state,
-        //     utils::State::new(5, "^(0(1)*)*$".to_string(), Vec::from([(1, 7), (3, 5)]), "".to_string())
-        // )
-    }
-
-    #[test]
-    fn update_test() {
-        let s = State::new(
-            0,
-            "^(abc)\x00(def)$".to_string(),
-            vec![(1, 5), (10, 14)],
-            Vec::new(),
-        );
-        let mut ext_parentheses = s.parentheses.clone();
-        utils::update_parentheses(&mut ext_parentheses, 7, 3, true);
-        assert_eq!(ext_parentheses, vec![(1, 5), (7, 11)]);
-    }
+    fn cli_reads_examples_from_arguments_and_files() {
+        let path: std::path::PathBuf = std::env::temp_dir().join("regexsynth_cli_negatives.txt");
+        std::fs::write(&path, "10\n1\n\n").unwrap();
+        let cli: cli::Cli = cli::Cli::try_parse_from([
+            "regexsynth",
+            "synth",
+            "-p",
+            "01",
+            "-p",
+            "0001",
+            "--negative-file",
+            path.to_str().unwrap(),
+            "--max-cost",
+            "6",
+            "--timeout",
+            "1.5",
+            "--plus",
+            "--quiet",
+        ])
+        .unwrap();
+        let cli::Command::Synth(args) = cli.command else {
+            panic!("expected the synth subcommand");
+        };
+        let tc: TestCase = args.test_case().unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-    #[test]
-    fn is_inside_or_works() {
-        let s: State = State::new(
-            4,
-            "^(((\x00|\x00|\x00))*)*$".to_string(),
-            [(1, 21), (2, 19), (3, 18)].to_vec(),
-            Vec::new(),
+        assert_eq!(tc.positive_set, vec!["01", "0001"]);
+        assert_eq!(tc.negative_set, vec!["10", "1", ""]);
+        assert_eq!(tc.alphabet, vec!['0', '1']);
+        assert_eq!(tc.limits.max_cost, Some(6));
+        assert_eq!(
+            tc.limits.timeout,
+            Some(std::time::Duration::from_millis(1500))
         );
-        assert!(utils::is_inside_or(&s, 14) == true);
+        assert_eq!(tc.grammar.productions.len(), 4);
+        assert!(!tc.verbose);
+        assert!(cli::Cli::try_parse_from([
+            "regexsynth",
+            "cost",
+            "01",
+            "--grammar",
+            "g.toml",
+            "--plus"
+        ])
+        .is_err());
     }
 
     #[test]
-    fn is_really_redundant() {
-        let s: State = State::new(0, r"^(0\x00)*$".to_string(), vec![], vec![]);
+    fn bench_times_out_unless_limited() {
+        let bench = |args: &[&str]| -> cli::BenchArgs {
+            let cli: cli::Cli =
+                cli::Cli::try_parse_from(["regexsynth", "bench"].iter().chain(args)).unwrap();
+            let cli::Command::Bench(args) = cli.command else {
+                panic!("expected the bench subcommand");
+            };
+            args
+        };
         assert_eq!(
-            utils::is_redundant(
-                &s.regexp,
-                &vec!["01", "01101", "0001"]
-                    .iter()
-                    .map(|&x| x.to_string())
-                    .collect(),
-            ),
-            false
+            bench(&[]).limits().timeout,
+            Some(std::time::Duration::from_secs(10))
         );
+        let limited: regexsynth::Limits = bench(&["--max-states", "500"]).limits();
+        assert_eq!(limited.timeout, None);
+        assert_eq!(limited.max_states, Some(500));
     }
     // #[test]
     // fn vec_bench() {
@@ -0,0 +1,359 @@
+use crate::utils::{Limits, SearchOptions, State, TestCase};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Writes a duration as fractional seconds.
+pub fn as_secs<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(d.as_secs_f64())
+}
+
+pub fn as_opt_secs<S: Serializer>(d: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => serializer.serialize_some(&d.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A successful synthesis run.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SynthOutcome {
+    /// the cheapest candidate consistent with every example
+    pub state: State,
+    /// `state.regexp` rendered in regex syntax
+    pub regex: String,
+    /// further consistent candidates, cheapest first, when the search was asked for several
+    pub alternatives: Vec<State>,
+    pub stats: SynthStats,
+}
+
+impl SynthOutcome {
+    pub fn new(state: State, stats: SynthStats) -> SynthOutcome {
+        let regex: String = state.regexp.to_string();
+        SynthOutcome {
+            state,
+            regex,
+            alternatives: Vec::new(),
+            stats,
+        }
+    }
+
+    /// Every consistent regex found, cheapest first.
+    pub fn regexes(&self) -> Vec<String> {
+        std::iter::once(&self.state)
+            .chain(&self.alternatives)
+            .map(|s: &State| s.regexp.to_string())
+            .collect()
+    }
+}
+
+impl Display for SynthOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.state)?;
+        for state in &self.alternatives {
+            write!(f, "\n{}", state)?;
+        }
+        Ok(())
+    }
+}
+
+/// States taken off the queue at one priority.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct LevelStats {
+    /// the queue priority: the cost so far, plus the bound on the open holes under A*, so not
+    /// the cost of any one regex
+    pub priority: usize,
+    pub states: usize,
+    /// time from the start of the search to the first state of this priority
+    #[serde(rename = "reached_after_secs", serialize_with = "as_secs")]
+    pub reached_after: Duration,
+}
+
+/// Counters gathered while searching.
+#[derive(Clone, PartialEq, Debug, Default, Serialize)]
+pub struct SynthStats {
+    /// states taken off the queue
+    pub total: usize,
+    pub leaf: usize,
+    pub dead: usize,
+    pub redundant: usize,
+    /// expansions dropped because the same regex was already queued
+    pub dedup_hits: usize,
+    /// expansions dropped for not being in canonical form
+    pub non_canonical: usize,
+    /// complete expansions dropped because one accepting the same examples was already queued
+    pub equivalent: usize,
+    /// one entry per queue priority reached, lowest first
+    pub levels: Vec<LevelStats>,
+    #[serde(rename = "elapsed_secs", serialize_with = "as_secs")]
+    pub elapsed: Duration,
+    /// estimated bytes held by the seen-regex and signature tables
+    pub table_bytes: usize,
+    /// estimated bytes held by the states queued or still in the batch, and the seen-regex and
+    /// signature tables at their largest
+    pub peak_memory: usize,
+}
+
+impl Display for SynthStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Total: {}, Leaf: {}, Dead: {}, Redundant: {}",
+            self.total, self.leaf, self.dead, self.redundant
+        )
+    }
+}
+
+/// Why `synth` did not produce a regex. The counters are boxed to keep `Result`s small.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SynthError {
+    /// every candidate up to the cost bound was tried
+    NoSolutionWithinBound(usize, Box<SynthStats>),
+    /// the grammar has no more candidates to offer
+    SearchExhausted(Box<SynthStats>),
+    /// the wall-clock budget ran out
+    Timeout(Duration, Box<SynthStats>),
+    /// the budget of expanded states ran out
+    StateLimit(usize, Box<SynthStats>),
+    /// the states queued or still in the batch, and the seen-regex and signature tables outgrew the
+    /// memory budget, in bytes
+    MemoryLimit(usize, Box<SynthStats>),
+    /// an example uses a symbol outside the alphabet
+    InvalidExample(String),
+    /// these strings are both positive and negative examples
+    ContradictoryExamples(Vec<String>),
+    /// a production is free or only reproduces a hole, or none is free of holes
+    InvalidGrammar(String),
+    /// a candidate or one of its approximations did not compile
+    InternalRegexError(String),
+    /// the worker threads for a parallel search could not be started
+    ThreadPool(String),
+}
+
+impl Display for SynthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynthError::NoSolutionWithinBound(max_cost, stats) => {
+                write!(f, "no solution within cost {} ({})", max_cost, stats)
+            }
+            SynthError::SearchExhausted(stats) => write!(f, "no solution ({})", stats),
+            SynthError::Timeout(limit, stats) => write!(
+                f,
+                "timed out after {} seconds ({})",
+                limit.as_secs_f32(),
+                stats
+            ),
+            SynthError::StateLimit(limit, stats) => {
+                write!(f, "gave up after {} states ({})", limit, stats)
+            }
+            SynthError::MemoryLimit(limit, stats) => {
+                write!(f, "gave up at {} bytes of search state ({})", limit, stats)
+            }
+            SynthError::InvalidExample(example) => {
+                write!(f, "example {:?} uses symbols outside the alphabet", example)
+            }
+            SynthError::ContradictoryExamples(examples) => {
+                write!(f, "examples are both positive and negative: {:?}", examples)
+            }
+            SynthError::InvalidGrammar(message) => write!(f, "invalid grammar: {}", message),
+            SynthError::InternalRegexError(message) => write!(f, "regex error: {}", message),
+            SynthError::ThreadPool(message) => {
+                write!(f, "could not start worker threads: {}", message)
+            }
+        }
+    }
+}
+
+impl SynthError {
+    /// A stable snake_case name for the variant, used in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SynthError::NoSolutionWithinBound(..) => "no_solution_within_bound",
+            SynthError::SearchExhausted(_) => "search_exhausted",
+            SynthError::Timeout(..) => "timeout",
+            SynthError::StateLimit(..) => "state_limit",
+            SynthError::MemoryLimit(..) => "memory_limit",
+            SynthError::InvalidExample(_) => "invalid_example",
+            SynthError::ContradictoryExamples(_) => "contradictory_examples",
+            SynthError::InvalidGrammar(_) => "invalid_grammar",
+            SynthError::InternalRegexError(_) => "internal_regex_error",
+            SynthError::ThreadPool(_) => "thread_pool",
+        }
+    }
+
+    /// Whether the examples or the grammar, rather than the search, are at fault.
+    pub fn is_input_error(&self) -> bool {
+        matches!(
+            self,
+            SynthError::InvalidExample(_)
+                | SynthError::ContradictoryExamples(_)
+                | SynthError::InvalidGrammar(_)
+        )
+    }
+
+    /// Counters gathered before the search stopped, if it got that far.
+    pub fn stats(&self) -> Option<&SynthStats> {
+        match self {
+            SynthError::NoSolutionWithinBound(_, stats)
+            | SynthError::SearchExhausted(stats)
+            | SynthError::Timeout(_, stats)
+            | SynthError::StateLimit(_, stats)
+            | SynthError::MemoryLimit(_, stats) => Some(stats.as_ref()),
+            SynthError::InvalidExample(_)
+            | SynthError::ContradictoryExamples(_)
+            | SynthError::InvalidGrammar(_)
+            | SynthError::InternalRegexError(_)
+            | SynthError::ThreadPool(_) => None,
+        }
+    }
+}
+
+impl std::error::Error for SynthError {}
+
+impl From<regex::Error> for SynthError {
+    fn from(e: regex::Error) -> SynthError {
+        SynthError::InternalRegexError(e.to_string())
+    }
+}
+
+/// A consistent regex found after the first one.
+#[derive(Clone, Debug, Serialize)]
+pub struct Alternative {
+    pub regex: String,
+    pub cost: usize,
+}
+
+/// One synthesis run in machine-readable form, as printed by `--format json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct SynthReport {
+    pub name: Option<String>,
+    /// `found`, or the `SynthError::kind` of the failure
+    pub status: &'static str,
+    pub regex: Option<String>,
+    pub cost: Option<usize>,
+    /// productions applied, in order
+    pub route: Vec<String>,
+    /// further consistent regexes and their costs, when several were asked for
+    pub alternatives: Vec<Alternative>,
+    pub expected: Option<String>,
+    /// whether `regex` is `expected`, when both are known
+    pub matches_expected: Option<bool>,
+    pub error: Option<String>,
+    pub stats: Option<SynthStats>,
+    /// wall-clock time of the whole run, example checks included
+    #[serde(rename = "elapsed_secs", serialize_with = "as_secs")]
+    pub elapsed: Duration,
+    pub limits: Limits,
+    pub pruning: bool,
+    pub astar: bool,
+    pub threads: Option<usize>,
+}
+
+impl SynthReport {
+    pub fn new(
+        case: &TestCase,
+        result: &Result<SynthOutcome, SynthError>,
+        elapsed: Duration,
+        options: &SearchOptions,
+    ) -> SynthReport {
+        let mut report: SynthReport = SynthReport {
+            name: case.name.clone(),
+            status: "found",
+            regex: None,
+            cost: None,
+            route: Vec::new(),
+            alternatives: Vec::new(),
+            expected: case.expected.clone(),
+            matches_expected: None,
+            error: None,
+            stats: None,
+            elapsed,
+            limits: case.limits.clone(),
+            pruning: options.pruning,
+            astar: options.astar,
+            threads: options.threads,
+        };
+
+        match result {
+            Ok(outcome) => {
+                report.regex = Some(outcome.regex.clone());
+                report.cost = Some(outcome.state.cost);
+                report.route = outcome.state.route.clone();
+                report.alternatives = outcome
+                    .alternatives
+                    .iter()
+                    .map(|s: &State| Alternative {
+                        regex: s.regexp.to_string(),
+                        cost: s.cost,
+                    })
+                    .collect();
+                report.matches_expected = case
+                    .expected
+                    .as_ref()
+                    .map(|expected: &String| *expected == outcome.regex);
+                report.stats = Some(outcome.stats.clone());
+            }
+            Err(e) => {
+                report.status = e.kind();
+                report.error = Some(e.to_string());
+                report.stats = e.stats().cloned();
+            }
+        }
+        report
+    }
+
+    /// Found a regex, and the expected one if the task names it.
+    pub fn succeeded(&self) -> bool {
+        self.regex.is_some() && self.matches_expected != Some(false)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn results_are_reported_as_json() {
+        let mut tc: TestCase = TestCase::from_lines("@expected ^(0|1)$\n+0\n+1\n-\n-01\n").unwrap();
+        tc.verbose = false;
+        tc.limits.timeout = Some(std::time::Duration::from_secs(5));
+        let result: Result<SynthOutcome, SynthError> = tc.synth(&utils::SearchOptions::default());
+        let report: SynthReport = SynthReport::new(
+            &tc,
+            &result,
+            std::time::Duration::from_millis(250),
+            &utils::SearchOptions::default(),
+        );
+        assert!(report.succeeded());
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["status"], "found");
+        assert_eq!(json["regex"], "^(0|1)$");
+        assert_eq!(json["cost"], 3);
+        assert_eq!(json["route"][0], r"^\x00$");
+        assert_eq!(json["matches_expected"], true);
+        assert_eq!(json["elapsed_secs"], 0.25);
+        assert_eq!(json["limits"]["timeout_secs"], 5.0);
+        assert_eq!(json["limits"]["max_cost"], serde_json::Value::Null);
+        assert_eq!(json["stats"]["levels"][0]["priority"], 0);
+
+        tc.limits.max_cost = Some(2);
+        let result: Result<SynthOutcome, SynthError> = tc.synth(&utils::SearchOptions::default());
+        let report: SynthReport = SynthReport::new(
+            &tc,
+            &result,
+            std::time::Duration::ZERO,
+            &utils::SearchOptions::default(),
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert!(!report.succeeded());
+        assert_eq!(json["status"], "no_solution_within_bound");
+        assert_eq!(json["regex"], serde_json::Value::Null);
+        assert!(json["stats"]["total"].as_u64().unwrap() > 0);
+    }
+}
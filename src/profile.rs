@@ -0,0 +1,206 @@
+use flame::Span;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Time spent in one instrumented function, summed over every call.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PhaseStats {
+    pub name: String,
+    pub calls: usize,
+    /// time inside the function, callees included; recursive calls are counted once
+    pub inclusive_ns: u64,
+    /// time inside the function itself, callees excluded
+    pub exclusive_ns: u64,
+}
+
+/// Per-phase totals of a profiled run, built from the `flame` spans recorded on every thread.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PhaseReport {
+    /// time spent in the outermost spans
+    pub total_ns: u64,
+    /// most inclusive time first
+    pub phases: Vec<PhaseStats>,
+}
+
+impl PhaseReport {
+    /// Aggregates the spans finished so far on the current thread and those committed by
+    /// other threads, such as the workers of a parallel search.
+    ///
+    /// The total is the current thread's, so with worker threads the phases can add up to more
+    /// than 100%.
+    pub fn collect() -> PhaseReport {
+        let threads: Vec<flame::Thread> = flame::threads();
+        let mut report: PhaseReport = PhaseReport::from_spans(&threads[0].spans);
+        report.phases = phases(threads.iter().flat_map(|t: &flame::Thread| &t.spans));
+        report
+    }
+
+    pub fn from_spans(spans: &[Span]) -> PhaseReport {
+        PhaseReport {
+            total_ns: spans.iter().map(|s: &Span| s.delta).sum(),
+            phases: phases(spans),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+fn phases<'a>(spans: impl IntoIterator<Item = &'a Span>) -> Vec<PhaseStats> {
+    let mut phases: BTreeMap<String, PhaseStats> = BTreeMap::new();
+    let mut active: Vec<&str> = Vec::new();
+    for span in spans {
+        add_span(span, &mut phases, &mut active);
+    }
+
+    let mut phases: Vec<PhaseStats> = phases.into_values().collect();
+    phases.sort_by_key(|p: &PhaseStats| std::cmp::Reverse(p.inclusive_ns));
+    phases
+}
+
+fn add_span<'a>(
+    span: &'a Span,
+    phases: &mut BTreeMap<String, PhaseStats>,
+    active: &mut Vec<&'a str>,
+) {
+    let children_ns: u64 = span.children.iter().map(|s: &Span| s.delta).sum();
+    let phase: &mut PhaseStats =
+        phases
+            .entry(span.name.to_string())
+            .or_insert_with(|| PhaseStats {
+                name: span.name.to_string(),
+                ..Default::default()
+            });
+    phase.calls += 1;
+    phase.exclusive_ns += span.delta.saturating_sub(children_ns);
+    // an enclosing call of the same function already covers this one
+    if !active.contains(&&*span.name) {
+        phase.inclusive_ns += span.delta;
+    }
+
+    active.push(&span.name);
+    for child in &span.children {
+        add_span(child, phases, active);
+    }
+    active.pop();
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * part as f64 / total as f64
+    }
+}
+
+impl Display for PhaseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>10} {:>14} {:>6} {:>14} {:>6} {:>12}",
+            "phase", "calls", "inclusive ms", "%", "exclusive ms", "%", "ns/call"
+        )?;
+        for phase in &self.phases {
+            writeln!(
+                f,
+                "{:<16} {:>10} {:>14.3} {:>6.1} {:>14.3} {:>6.1} {:>12}",
+                phase.name,
+                phase.calls,
+                phase.inclusive_ns as f64 / 1e6,
+                percent(phase.inclusive_ns, self.total_ns),
+                phase.exclusive_ns as f64 / 1e6,
+                percent(phase.exclusive_ns, self.total_ns),
+                phase.inclusive_ns / phase.calls as u64
+            )?;
+        }
+        write!(f, "total {:.3} ms", self.total_ns as f64 / 1e6)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::TestCase;
+    use crate::Synthesizer;
+    use std::sync::Mutex;
+
+    // `flame::clear` also drops the spans committed by other threads
+    static FLAME: Mutex<()> = Mutex::new(());
+
+    fn calls(report: &PhaseReport, name: &str) -> usize {
+        report
+            .phases
+            .iter()
+            .find(|p: &&PhaseStats| p.name == name)
+            .map_or(0, |p: &PhaseStats| p.calls)
+    }
+
+    #[test]
+    fn phase_report_counts_recursion_once() {
+        let _flame = FLAME.lock().unwrap();
+        flame::clear();
+        flame::span_of("outer", || {
+            flame::span_of("inner", || {
+                flame::span_of("inner", || {
+                    std::thread::sleep(std::time::Duration::from_millis(2))
+                })
+            });
+            flame::span_of("inner", || ());
+        });
+        let report: PhaseReport = PhaseReport::collect();
+        let phase = |name: &str| -> &PhaseStats {
+            report
+                .phases
+                .iter()
+                .find(|p: &&PhaseStats| p.name == name)
+                .unwrap()
+        };
+        let spans: Vec<flame::Span> = flame::spans();
+
+        assert_eq!(report.phases[0].name, "outer");
+        assert_eq!(report.total_ns, spans[0].delta);
+        assert_eq!(phase("outer").calls, 1);
+        assert_eq!(phase("inner").calls, 3);
+        let inner: &flame::Span = &spans[0].children[0];
+        assert_eq!(
+            phase("inner").inclusive_ns,
+            inner.delta + spans[0].children[1].delta
+        );
+        assert_eq!(
+            phase("outer").exclusive_ns + phase("inner").exclusive_ns,
+            report.total_ns
+        );
+        assert!(report.to_string().starts_with("phase"));
+    }
+
+    #[test]
+    fn phase_report_includes_worker_threads() {
+        let _flame = FLAME.lock().unwrap();
+        let tc: TestCase = TestCase::from_lines("+01\n+0001\n-10\n-1\n").unwrap();
+        let synthesizer: Synthesizer = Synthesizer::from_test_case(&tc)
+            .pruning(true)
+            .verbose(false);
+        let mut reports: Vec<PhaseReport> = Vec::new();
+        for threads in [None, Some(2)] {
+            flame::clear();
+            let mut synthesizer: Synthesizer = synthesizer.clone();
+            if let Some(threads) = threads {
+                synthesizer = synthesizer.threads(threads);
+            }
+            synthesizer.run().unwrap();
+            reports.push(PhaseReport::collect());
+        }
+        let (sequential, parallel): (&PhaseReport, &PhaseReport) = (&reports[0], &reports[1]);
+        // each worker commits its spans once per search
+        assert!(flame::threads().len() <= 3);
+
+        assert_eq!(calls(parallel, "synth"), 1);
+        for name in ["is_dead", "is_redundant", "expand"] {
+            assert!(calls(sequential, name) > 0, "{} missing", name);
+            // a batch is examined whole, even past the state that ends the search
+            assert!(calls(parallel, name) >= calls(sequential, name), "{}", name);
+        }
+    }
+}
@@ -1,14 +1,15 @@
 use cute::{self, c};
-use rand::Rng;
-use random_string::generate;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use regex::Regex;
+use regexsynth::utils;
 use std::time::Instant;
 
-pub fn regex_benchmarking() {
-    let charset = "01";
-    let set_len: i32 = 10000000;
+pub fn regex_benchmarking(set_len: usize, seed: u64) {
+    let charset: [char; 2] = ['0', '1'];
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(seed);
     let string_set: Vec<String> =
-        c![generate(rand::thread_rng().gen_range(1..50), charset), for _x in 0..set_len];
+        c![utils::random_string(&mut rng, 1..50, &charset), for _x in 0..set_len];
     let regex_set: [&str; 4] = [
         r"^(0(1)*)*$",
         r"^((1)*0)*1$",
@@ -18,9 +19,8 @@ pub fn regex_benchmarking() {
 
     for regexp in regex_set {
         let start: Instant = Instant::now();
-        string_set
-            .iter()
-            .any(|x: &String| Regex::new(regexp).unwrap().is_match(x));
+        let re: Regex = Regex::new(regexp).unwrap();
+        string_set.iter().any(|x: &String| re.is_match(x));
         let elapsed_secs: f32 = start.elapsed().as_secs_f32();
         println!(
             "finished in {} sec, set len = {}",
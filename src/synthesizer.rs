@@ -0,0 +1,264 @@
+use crate::cost::CostModel;
+use crate::grammar::{ExtraProductions, Grammar};
+use crate::outcome::{SynthError, SynthOutcome};
+use crate::utils::{self, Limits, SearchOptions, Solutions, TestCase};
+use std::time::Duration;
+
+/// Configures and runs one synthesis problem.
+///
+/// ```
+/// use regexsynth::Synthesizer;
+///
+/// let outcome = Synthesizer::new()
+///     .positive(["01", "01101", "0001"])
+///     .negative(["10", "1", "11010"])
+///     .max_cost(6)
+///     .run()
+///     .unwrap();
+/// assert_eq!(outcome.regex, "^(0(1)*)*$");
+/// ```
+///
+/// The alphabet is inferred from the examples unless set, and the grammar is the built-in one
+/// priced by the cost model unless a whole grammar is given, which then takes precedence.
+#[derive(Clone, Debug, Default)]
+pub struct Synthesizer {
+    positive_set: Vec<String>,
+    negative_set: Vec<String>,
+    alphabet: Option<Vec<char>>,
+    grammar: Option<Grammar>,
+    extra: ExtraProductions,
+    costs: CostModel,
+    limits: Limits,
+    options: SearchOptions,
+}
+
+impl Synthesizer {
+    pub fn new() -> Synthesizer {
+        Synthesizer::default()
+    }
+
+    /// Starts from `case`'s examples, alphabet, grammar, limits and verbosity.
+    pub fn from_test_case(case: &TestCase) -> Synthesizer {
+        Synthesizer::new()
+            .positive(case.positive_set.iter().cloned())
+            .negative(case.negative_set.iter().cloned())
+            .alphabet(case.alphabet.iter().copied())
+            .grammar(case.grammar.clone())
+            .limits(case.limits.clone())
+            .verbose(case.verbose)
+    }
+
+    /// Adds strings the regex must accept.
+    pub fn positive<I, S>(mut self, examples: I) -> Synthesizer
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.positive_set
+            .extend(examples.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds strings the regex must reject.
+    pub fn negative<I, S>(mut self, examples: I) -> Synthesizer
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.negative_set
+            .extend(examples.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn alphabet<I: IntoIterator<Item = char>>(mut self, alphabet: I) -> Synthesizer {
+        self.alphabet = Some(alphabet.into_iter().collect());
+        self
+    }
+
+    pub fn grammar(mut self, grammar: Grammar) -> Synthesizer {
+        self.grammar = Some(grammar);
+        self
+    }
+
+    pub fn extra_productions(mut self, extra: ExtraProductions) -> Synthesizer {
+        self.extra = extra;
+        self
+    }
+
+    pub fn cost_model(mut self, costs: CostModel) -> Synthesizer {
+        self.costs = costs;
+        self
+    }
+
+    /// Skip candidates that `is_dead` rules out, or `is_redundant` when the grammar is
+    /// elementary (see `Grammar::is_elementary`).
+    pub fn pruning(mut self, pruning: bool) -> Synthesizer {
+        self.options.pruning = pruning;
+        self
+    }
+
+    /// Print every pruned candidate.
+    pub fn debug(mut self, debug: bool) -> Synthesizer {
+        self.options.debug = debug;
+        self
+    }
+
+    /// Print the progress table and the final counters.
+    pub fn verbose(mut self, verbose: bool) -> Synthesizer {
+        self.options.verbose = verbose;
+        self
+    }
+
+    /// Expand states in order of cost plus a lower bound on filling their holes. This finds a
+    /// regex of the same cost, usually after fewer states.
+    pub fn astar(mut self, astar: bool) -> Synthesizer {
+        self.options.astar = astar;
+        self
+    }
+
+    /// Check the states of each cost on `threads` worker threads, `0` for one per core. The
+    /// search takes the same course as on a single thread, so the result does not change.
+    pub fn threads(mut self, threads: usize) -> Synthesizer {
+        self.options.threads = Some(threads);
+        self
+    }
+
+    /// Which consistent regexes to collect; see [`Solutions`].
+    pub fn solutions(mut self, solutions: Solutions) -> Synthesizer {
+        self.options.solutions = solutions;
+        self
+    }
+
+    /// Of several consistent regexes accepting the same language, keep only the cheapest.
+    pub fn distinct_languages(mut self, distinct: bool) -> Synthesizer {
+        self.options.distinct_languages = distinct;
+        self
+    }
+
+    /// Replaces every search switch at once.
+    pub fn options(mut self, options: SearchOptions) -> Synthesizer {
+        self.options = options;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Synthesizer {
+        self.limits = limits;
+        self
+    }
+
+    pub fn max_cost(mut self, max_cost: usize) -> Synthesizer {
+        self.limits.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Synthesizer {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_states(mut self, max_states: usize) -> Synthesizer {
+        self.limits.max_states = Some(max_states);
+        self
+    }
+
+    /// Bound on the estimated bytes held by the states queued or still in the batch, and the
+    /// seen-regex and signature tables.
+    pub fn max_memory(mut self, max_memory: usize) -> Synthesizer {
+        self.limits.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Searches for the cheapest regex consistent with the examples.
+    pub fn run(&self) -> Result<SynthOutcome, SynthError> {
+        let alphabet: Vec<char> = match &self.alphabet {
+            Some(alphabet) => alphabet.clone(),
+            None => utils::infer_alphabet(&self.positive_set, &self.negative_set),
+        };
+        let grammar: Grammar = match &self.grammar {
+            Some(grammar) => grammar.clone(),
+            None => Grammar::new(&self.extra, &self.costs).map_err(SynthError::InvalidGrammar)?,
+        };
+
+        utils::synth(
+            &self.positive_set,
+            &self.negative_set,
+            &alphabet,
+            &grammar,
+            &self.limits,
+            &self.options,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cost, grammar, outcome};
+
+    #[test]
+    fn synthesizer_builder_configures_the_search() {
+        let synthesizer: Synthesizer = Synthesizer::new()
+            .positive(["10", "110", "1110"])
+            .negative(vec!["0".to_string(), "1".to_string(), "100".to_string()])
+            .extra_productions(grammar::ExtraProductions {
+                plus: true,
+                ..Default::default()
+            });
+        let outcome: outcome::SynthOutcome = synthesizer.run().unwrap();
+        assert_eq!(outcome.regex, "^(1)+0$");
+
+        let outcome: outcome::SynthOutcome = synthesizer
+            .clone()
+            .cost_model(cost::CostModel::from_toml("plus = 4").unwrap())
+            .run()
+            .unwrap();
+        assert_eq!(outcome.regex, "^1(1)*0$");
+
+        let bounded: Result<outcome::SynthOutcome, outcome::SynthError> = synthesizer
+            .clone()
+            .alphabet(['0', '1', '2'])
+            .max_cost(3)
+            .run();
+        assert!(matches!(
+            bounded,
+            Err(outcome::SynthError::NoSolutionWithinBound(3, _))
+        ));
+        let overridden: outcome::SynthOutcome = synthesizer
+            .grammar(grammar::Grammar::default())
+            .run()
+            .unwrap();
+        assert_eq!(overridden.regex, "^1(1)*0$");
+    }
+
+    #[test]
+    fn free_productions_are_rejected() {
+        let synthesizer: Synthesizer = Synthesizer::new().positive(["0"]).negative(["1"]);
+        let free_star: cost::CostModel = cost::CostModel {
+            star: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            synthesizer.clone().cost_model(free_star).run(),
+            Err(outcome::SynthError::InvalidGrammar(_))
+        ));
+
+        let mut free_concat: grammar::Grammar = grammar::Grammar::default();
+        free_concat.productions[1].cost = 0;
+        let e: outcome::SynthError = synthesizer.clone().grammar(free_concat).run().unwrap_err();
+        assert_eq!(e.kind(), "invalid_grammar");
+        assert!(e.is_input_error());
+
+        // the alphabet inferred from `[""]` leaves no terminal to fill a hole with
+        assert!(matches!(
+            Synthesizer::new().positive([""]).run(),
+            Err(outcome::SynthError::InvalidGrammar(_))
+        ));
+        let no_literals: grammar::Grammar =
+            grammar::Grammar::from_toml("[[production]]\npattern = '(\\x00)*'\ncost = 1\n")
+                .unwrap();
+        assert!(matches!(
+            synthesizer.grammar(no_literals).run(),
+            Err(outcome::SynthError::InvalidGrammar(_))
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::utils::{infer_alphabet, TestCase};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Task files hold the examples for one synthesis problem, written as TOML:
+///
+/// ```toml
+/// name = "start_with_0"
+/// alphabet = "01"            # optional, inferred from the examples when omitted
+/// expected = '^(0(1)*)*$'   # optional
+/// positive = ["01", "01101", "0001"]
+/// negative = ["10", "1", "11010"]
+/// ```
+///
+/// or as JSON with the same keys (for `.json` files), or one example per line (any other
+/// extension): `+x` is a positive example, `-x` a negative one, `@name`, `@alphabet` and
+/// `@expected` followed by a space and a value set the metadata, and blank lines and lines
+/// starting with `#` are skipped.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alphabet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected: Option<String>,
+    #[serde(default)]
+    positive: Vec<String>,
+    #[serde(default)]
+    negative: Vec<String>,
+}
+
+impl TestCase {
+    pub fn from_toml(text: &str) -> Result<TestCase, String> {
+        let file: TaskFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        Ok(TestCase::from_file(file))
+    }
+
+    pub fn from_json(text: &str) -> Result<TestCase, String> {
+        let file: TaskFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        Ok(TestCase::from_file(file))
+    }
+
+    pub fn from_lines(text: &str) -> Result<TestCase, String> {
+        let mut file: TaskFile = TaskFile {
+            name: None,
+            alphabet: None,
+            expected: None,
+            positive: Vec::new(),
+            negative: Vec::new(),
+        };
+
+        for (i, line) in text.lines().enumerate() {
+            if let Some(x) = line.strip_prefix('+') {
+                file.positive.push(x.to_string());
+            } else if let Some(x) = line.strip_prefix('-') {
+                file.negative.push(x.to_string());
+            } else if let Some(directive) = line.strip_prefix('@') {
+                let (key, value): (&str, &str) =
+                    directive.split_once(' ').unwrap_or((directive, ""));
+                let field: &mut Option<String> = match key {
+                    "name" => &mut file.name,
+                    "alphabet" => &mut file.alphabet,
+                    "expected" => &mut file.expected,
+                    _ => return Err(format!("line {}: unknown directive '@{}'", i + 1, key)),
+                };
+                *field = Some(value.to_string());
+            } else if !line.is_empty() && !line.starts_with('#') {
+                return Err(format!(
+                    "line {}: expected a line starting with '+', '-', '@' or '#'",
+                    i + 1
+                ));
+            }
+        }
+
+        Ok(TestCase::from_file(file))
+    }
+
+    /// Loads a task, reading `.json` files as JSON, `.toml` files as TOML and anything else
+    /// as `+`/`-` lines.
+    pub fn load(path: &Path) -> Result<TestCase, String> {
+        let text: String = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => TestCase::from_json(&text),
+            Some("toml") => TestCase::from_toml(&text),
+            _ => TestCase::from_lines(&text),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.to_file()).unwrap()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_file()).unwrap()
+    }
+
+    pub fn to_lines(&self) -> String {
+        let file: TaskFile = self.to_file();
+        let mut out: String = String::new();
+        for (key, value) in [
+            ("name", &file.name),
+            ("alphabet", &file.alphabet),
+            ("expected", &file.expected),
+        ] {
+            if let Some(value) = value {
+                out.push_str(&format!("@{} {}\n", key, value));
+            }
+        }
+        for x in &file.positive {
+            out.push_str(&format!("+{}\n", x));
+        }
+        for x in &file.negative {
+            out.push_str(&format!("-{}\n", x));
+        }
+        out
+    }
+
+    fn from_file(file: TaskFile) -> TestCase {
+        let alphabet: Vec<char> = match file.alphabet {
+            Some(alphabet) => alphabet.chars().collect(),
+            None => infer_alphabet(&file.positive, &file.negative),
+        };
+        let mut case: TestCase = TestCase::with_alphabet(file.positive, file.negative, alphabet);
+        case.name = file.name;
+        case.expected = file.expected;
+        case
+    }
+
+    fn to_file(&self) -> TaskFile {
+        TaskFile {
+            name: self.name.clone(),
+            alphabet: Some(self.alphabet.iter().collect()),
+            expected: self.expected.clone(),
+            positive: self.positive_set.clone(),
+            negative: self.negative_set.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn task_files_round_trip() {
+        let lines: &str =
+            "# start_with_0\n@name start_with_0\n@expected ^(0(1)*)*$\n+01\n+0001\n-10\n-\n";
+        let tc: TestCase = TestCase::from_lines(lines).unwrap();
+        assert_eq!(tc.name.as_deref(), Some("start_with_0"));
+        assert_eq!(tc.expected.as_deref(), Some("^(0(1)*)*$"));
+        assert_eq!(tc.positive_set, vec!["01", "0001"]);
+        assert_eq!(tc.negative_set, vec!["10", ""]);
+        assert_eq!(tc.alphabet, vec!['0', '1']);
+
+        for copy in [
+            TestCase::from_lines(&tc.to_lines()).unwrap(),
+            TestCase::from_toml(&tc.to_toml()).unwrap(),
+            TestCase::from_json(&tc.to_json()).unwrap(),
+        ] {
+            assert_eq!(copy.name, tc.name);
+            assert_eq!(copy.expected, tc.expected);
+            assert_eq!(copy.positive_set, tc.positive_set);
+            assert_eq!(copy.negative_set, tc.negative_set);
+            assert_eq!(copy.alphabet, tc.alphabet);
+        }
+
+        let toml: TestCase =
+            TestCase::from_toml("alphabet = \"012\"\npositive = [\"1\"]\nnegative = []").unwrap();
+        assert_eq!(toml.alphabet, vec!['0', '1', '2']);
+        assert!(TestCase::from_toml("positive = [\"1\"]\nexpexted = \"1\"").is_err());
+        assert!(TestCase::from_lines("+01\n01\n").is_err());
+    }
+}
@@ -1,51 +1,69 @@
-#[allow(dead_code)]
+use crate::ast::Node;
+use crate::grammar::Grammar;
+use crate::language;
+use crate::outcome::{as_opt_secs, LevelStats, SynthError, SynthOutcome, SynthStats};
+use crate::synthesizer::Synthesizer;
+#[cfg(feature = "profiling")]
 use flamer::flame;
-use rand::{seq::index, Rng};
-use random_string::generate;
+use rand::Rng;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Display;
-use std::mem::replace;
-use std::string;
+use std::num::NonZeroUsize;
+use std::ops::Range;
 use std::time::{Duration, Instant};
-use std::{collections::HashSet, fmt::write, vec};
 
-// substitute, cost, pan_dist, pan_backwards
-const ALL_SUB: [(&'static str, usize, usize, bool); 5] = [
-    (r"0", 1, 3, true),
-    (r"1", 1, 3, true),
-    (r"(\x00)*", 1, 3, false),
-    (r"\x00\x00", 1, 4, false),
-    (r"(\x00|\x00)", 1, 7, false),
-];
+/// Collects every distinct symbol used in the examples, in sorted order.
+pub fn infer_alphabet(positive_set: &[String], negative_set: &[String]) -> Vec<char> {
+    let symbols: BTreeSet<char> = positive_set
+        .iter()
+        .chain(negative_set.iter())
+        .flat_map(|x: &String| x.chars())
+        .collect();
+
+    symbols.into_iter().collect()
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct State {
     pub cost: usize,
-    pub regexp: String,
+    pub regexp: Node,
     is_leaf: bool,
-    pub parentheses: Vec<(usize, usize)>,
     pub route: Vec<String>,
+    /// which examples a complete regex accepts, once the search has matched it against them
+    signature: Option<Vec<bool>>,
 }
 
 impl State {
-    pub fn new(
-        cost: usize,
-        regexp: String,
-        parentheses: Vec<(usize, usize)>,
-        route: Vec<String>,
-    ) -> State {
-        let is_leaf: bool = !regexp.contains(r"\x00");
+    pub fn new(cost: usize, regexp: Node, route: Vec<String>) -> State {
+        let is_leaf: bool = regexp.is_complete();
 
         State {
             cost,
             regexp,
             is_leaf,
-            parentheses,
             route,
+            signature: None,
         }
     }
 }
 
+impl State {
+    /// Estimated bytes held by this state, route included.
+    pub fn footprint(&self) -> usize {
+        let route: usize = self
+            .route
+            .iter()
+            .map(|x: &String| std::mem::size_of::<String>() + x.capacity())
+            .sum();
+        let signature: usize = self.signature.as_ref().map_or(0, Vec::capacity);
+        std::mem::size_of::<State>() + self.regexp.footprint() + route + signature
+    }
+}
+
 impl Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -56,397 +74,1314 @@ impl Display for State {
     }
 }
 
-#[derive(Debug)]
+/// Bucket queue indexed by priority; buckets are added as pricier states arrive, so there is no
+/// fixed cost ceiling. States come out in order of priority, and FIFO within one priority.
+///
+/// The priority is the cost so far plus `hole_cost` for every open hole, so with `hole_cost` at
+/// most the cost of filling a hole completely the queue runs an A* search, and with `0` it is
+/// ordered by cost alone.
+#[derive(Debug, Default)]
 pub struct Queue {
-    q: Vec<Vec<State>>,
+    q: Vec<VecDeque<State>>,
     cost: usize,
-    index: usize,
+    bytes: usize,
+    hole_cost: usize,
 }
 
 impl Queue {
-    pub fn new(max_cost: usize) -> Queue {
-        let q: Vec<Vec<State>> = vec![vec![]; max_cost];
-        let cost: usize = 0;
-        let index: usize = 0;
-        Queue { q, cost, index }
+    pub fn new() -> Queue {
+        Queue::default()
     }
 
-    pub fn pop(&mut self) -> Option<State> {
-        if let Some(level) = self.q.get(self.cost) {
-            if let Some(item) = level.get(self.index) {
-                self.index += 1;
-                return Some(item.clone());
-            }
+    pub fn with_hole_cost(hole_cost: usize) -> Queue {
+        Queue {
+            hole_cost,
+            ..Queue::default()
         }
+    }
 
-        self.q[self.cost] = replace(&mut self.q[self.cost], vec![]);
-        self.cost += 1;
-        self.index = 0;
+    pub fn priority(&self, s: &State) -> usize {
+        s.cost + self.hole_cost * s.regexp.holes()
+    }
 
-        if let Some(level) = self.q.get(self.cost) {
-            if let Some(item) = level.get(self.index) {
-                self.index += 1;
-                return Some(item.clone());
+    pub fn pop(&mut self) -> Option<State> {
+        while let Some(level) = self.q.get_mut(self.cost) {
+            if let Some(item) = level.pop_front() {
+                self.bytes -= item.footprint();
+                return Some(item);
             }
+            self.cost += 1;
         }
         None
     }
 
     pub fn push(&mut self, s: State) {
-        self.q[s.cost].push(s)
+        // every production costs at least 1 and fills a hole at no more than its bound, so
+        // nothing lands below the level being popped
+        let cost: usize = self.priority(&s).max(self.cost);
+        if cost >= self.q.len() {
+            self.q.resize_with(cost + 1, VecDeque::new);
+        }
+        self.bytes += s.footprint();
+        self.q[cost].push_back(s)
+    }
+
+    /// Estimated bytes held by the queued states.
+    pub fn footprint(&self) -> usize {
+        self.bytes
+    }
+
+    /// Takes up to `max` states off the queue, all of the lowest priority, in queue order.
+    pub fn pop_batch(&mut self, max: usize) -> Vec<State> {
+        let mut batch: Vec<State> = Vec::new();
+        if let Some(first) = self.pop() {
+            batch.push(first);
+            // `pop` left `cost` at the bucket it took from
+            while batch.len() < max {
+                match self.q[self.cost].pop_front() {
+                    Some(item) => {
+                        self.bytes -= item.footprint();
+                        batch.push(item);
+                    }
+                    None => break,
+                }
+            }
+        }
+        batch
     }
 
     pub fn is_empty(&self) -> bool {
-        self.q.iter().all(|v| v.is_empty())
+        self.q.iter().skip(self.cost).all(|v| v.is_empty())
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TestCase {
+    pub name: Option<String>,
     pub positive_set: Vec<String>,
     pub negative_set: Vec<String>,
+    pub alphabet: Vec<char>,
+    /// the regex the search should find, if known
+    pub expected: Option<String>,
+    pub grammar: Grammar,
+    pub limits: Limits,
+    /// print search progress and the result
+    pub verbose: bool,
 }
 
 impl TestCase {
     pub fn new(positive_set: Vec<String>, negative_set: Vec<String>) -> TestCase {
+        let alphabet: Vec<char> = infer_alphabet(&positive_set, &negative_set);
+        TestCase::with_alphabet(positive_set, negative_set, alphabet)
+    }
+
+    pub fn with_alphabet(
+        positive_set: Vec<String>,
+        negative_set: Vec<String>,
+        alphabet: Vec<char>,
+    ) -> TestCase {
         TestCase {
+            name: None,
             positive_set,
             negative_set,
+            alphabet,
+            expected: None,
+            grammar: Grammar::default(),
+            limits: Limits::default(),
+            verbose: true,
         }
     }
 
-    pub fn synth(&self, debug: bool, pruning: bool) -> State {
+    /// Runs the search with `options`, printing the result when the case is verbose; the
+    /// case's own `verbose` takes the place of `options.verbose`.
+    pub fn synth(&self, options: &SearchOptions) -> Result<SynthOutcome, SynthError> {
         let start: Instant = Instant::now();
-        let state: State = synth(&self.positive_set, &self.negative_set, debug, pruning);
+        let result: Result<SynthOutcome, SynthError> = Synthesizer::from_test_case(self)
+            .options(options.clone())
+            .verbose(self.verbose)
+            .run();
         let elapsed: Duration = start.elapsed();
         let elapsed_secs: f32 = elapsed.as_secs_f32();
-        println!("{}", state);
-        println!("finished in {} seconds.", elapsed_secs);
-        state
-    }
-}
-
-#[inline(never)]
-#[flame]
-pub fn find_parentheses(regexp: &String, or_only: bool) -> Vec<(usize, usize)> {
-    let mut stack: Vec<usize> = Vec::new();
-    let mut indices: Vec<(usize, usize)> = Vec::new();
-
-    for (index, c) in regexp.char_indices() {
-        if c == '(' {
-            stack.push(index);
-        } else if c == ')' {
-            if let Some(start_index) = stack.pop() {
-                indices.push((start_index, index));
+        if self.verbose {
+            match &result {
+                Ok(outcome) => println!("{}", outcome),
+                Err(e) => println!("{}", e),
             }
+            println!("finished in {} seconds.", elapsed_secs);
         }
+        result
     }
+}
 
-    if or_only {
-        indices.retain(|(start, end)| {
-            regexp[*start..*end + 1].contains("|")
-                && !regexp[*start + 1..*end].contains(|c| c == '(' || c == ')')
-                && !regexp[*start..*end + 2].ends_with("*")
-        })
-    }
+/// Which consistent regexes a search returns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Solutions {
+    /// the first cheapest one found
+    #[default]
+    First,
+    /// every one of the cheapest cost
+    AllMinimal,
+    /// the `k` cheapest, whatever their cost
+    Cheapest(NonZeroUsize),
+}
 
-    indices
+/// Switches for one synthesis run.
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    /// skip states that `is_dead` or, for an elementary grammar, `is_redundant` rule out
+    pub pruning: bool,
+    /// print every pruned state
+    pub debug: bool,
+    /// print the `priority,sec,state_num` progress table and the final counters
+    pub verbose: bool,
+    /// order the queue by cost plus a lower bound on filling the open holes
+    pub astar: bool,
+    /// check states on a pool of this many worker threads, `0` for one per core; the result
+    /// is the same as searching on the calling thread, which `None` does
+    pub threads: Option<usize>,
+    pub solutions: Solutions,
+    /// of several consistent regexes accepting the same language, keep only the cheapest
+    pub distinct_languages: bool,
+}
+
+/// Budgets that end the search early; `None` means unlimited.
+#[derive(Clone, PartialEq, Debug, Default, Serialize)]
+pub struct Limits {
+    /// give up once every candidate up to this cost has been tried
+    pub max_cost: Option<usize>,
+    #[serde(rename = "timeout_secs", serialize_with = "as_opt_secs")]
+    pub timeout: Option<Duration>,
+    /// number of states taken off the queue
+    pub max_states: Option<usize>,
+    /// estimated bytes held by the states queued or still in the batch, and the seen-regex and
+    /// signature tables
+    pub max_memory: Option<usize>,
 }
 
 #[inline(never)]
-#[flame]
-pub fn is_inside_or(s: &State, index: usize) -> bool {
-    // check if the extension will be implemented inside or, if yes then the modification will be different e.g., (1|2)* -> (1|2|3)*
-    if &s.regexp[index - 1..index + 5] == r"(\x00|"
-        || &s.regexp[index - 1..index + 5] == r"|\x00|"
-        || &s.regexp[index - 1..index + 5] == r"|\x00)"
-    {
-        return true;
-    }
+#[cfg_attr(feature = "profiling", flame)]
+pub fn match_all(re: &Regex, positive_set: &[String]) -> bool {
+    positive_set.iter().all(|x: &String| re.is_match(x))
+}
 
-    false
+#[inline(never)]
+#[cfg_attr(feature = "profiling", flame)]
+pub fn match_none(re: &Regex, negative_set: &[String]) -> bool {
+    !negative_set.iter().any(|x: &String| re.is_match(x))
 }
 
+// Every operator is monotone in its operands, so reading holes as `Σ*` gives a language
+// containing every completion's and reading them as the empty language one contained in every
+// completion's. A positive rejected by the first, or a negative accepted by the second,
+// therefore rules out the whole subtree. Each partial regex is checked once, so the
+// approximations are matched on the tree rather than compiled.
 #[inline(never)]
-#[flame]
-pub fn update_parentheses(
-    parentheses: &mut Vec<(usize, usize)>,
-    x: usize,
-    pan_dist: usize,
-    pan_backwards: bool,
-) {
-    for tuple in parentheses.iter_mut() {
-        if tuple.0 > x && pan_backwards {
-            tuple.0 = tuple.0 - pan_dist;
-        }
-        if tuple.1 > x && pan_backwards {
-            tuple.1 = tuple.1 - pan_dist;
+#[cfg_attr(feature = "profiling", flame)]
+pub fn is_dead(
+    regexp: &Node,
+    positive_set: &[String],
+    negative_set: &[String],
+    alphabet: &[char],
+) -> bool {
+    !positive_set
+        .iter()
+        .all(|x: &String| regexp.accepts(x, Some(alphabet)))
+        || negative_set
+            .iter()
+            .any(|x: &String| regexp.accepts(x, None))
+}
+
+/// `x` forced to be used: `x x*` for `x*` and `x` for `x?`.
+fn used(regexp: &Node) -> Option<Node> {
+    match regexp {
+        Node::Star(inner) => Some(Node::concat((**inner).clone(), regexp.clone())),
+        Node::Optional(inner) => Some((**inner).clone()),
+        _ => None,
+    }
+}
+
+/// Collects one variant of `regexp` per part a cheaper regex could do without, with `rebuild`
+/// putting each variant back into its surroundings:
+/// - every alternation branch, at any depth, in place of its alternation;
+/// - every `*` or `?` inside a concatenation, used at least once;
+/// - every `?` inside a concatenation, left empty.
+///
+/// A part nested in `x*` or `x+` only has to be used in one iteration, so the repetition is
+/// expanded to `x* y x*` around the variant `y` of its body.
+fn collect_variants(regexp: &Node, rebuild: &dyn Fn(Node) -> Node, results: &mut Vec<Node>) {
+    match regexp {
+        Node::Alt(branches) => {
+            // a part of one branch is only used when that branch is taken
+            for branch in branches {
+                results.push(rebuild(branch.clone()));
+                collect_variants(branch, rebuild, results);
+            }
         }
-        if tuple.0 > x && !pan_backwards {
-            tuple.0 = tuple.0 + pan_dist;
+        Node::Concat(left, right) => {
+            if let Some(x) = used(left) {
+                results.push(rebuild(Node::concat(x, (**right).clone())));
+            }
+            if let Some(x) = used(right) {
+                results.push(rebuild(Node::concat((**left).clone(), x)));
+            }
+            if let Node::Optional(_) = **left {
+                results.push(rebuild((**right).clone()));
+            }
+            if let Node::Optional(_) = **right {
+                results.push(rebuild((**left).clone()));
+            }
+            collect_variants(
+                left,
+                &|x: Node| rebuild(Node::concat(x, (**right).clone())),
+                results,
+            );
+            collect_variants(
+                right,
+                &|x: Node| rebuild(Node::concat((**left).clone(), x)),
+                results,
+            );
         }
-        if tuple.1 > x && !pan_backwards {
-            tuple.1 = tuple.1 + pan_dist;
+        Node::Star(inner) | Node::Plus(inner) => {
+            let star: Node = Node::star((**inner).clone());
+            collect_variants(
+                inner,
+                &|x: Node| rebuild(Node::concat(star.clone(), Node::concat(x, star.clone()))),
+                results,
+            );
         }
+        Node::Optional(inner) => collect_variants(inner, rebuild, results),
+        Node::Hole | Node::Literal(_) | Node::Any | Node::Class(_) => {}
     }
 }
 
 #[inline(never)]
-#[flame]
-pub fn match_all(regexp: &str, positive_set: &Vec<String>) -> bool {
-    positive_set
-        .iter()
-        .all(|x: &String| Regex::new(regexp).unwrap().is_match(x))
+#[cfg_attr(feature = "profiling", flame)]
+pub fn variants(regexp: &Node) -> Vec<Node> {
+    let mut results: Vec<Node> = Vec::new();
+    collect_variants(regexp, &|x: Node| x, &mut results);
+    results
 }
 
+// Each variant accepts exactly the strings that need its part. If no positive example is among
+// them, even with holes as any string, then dropping the part (or the `?` around it) leaves a
+// cheaper regex that still accepts every positive, and rejects every negative as its language
+// only shrank, so no completion of `regexp` is a cheapest solution. Like `is_dead`, this
+// matches the variants on the tree, as each is only ever checked once.
 #[inline(never)]
-#[flame]
-pub fn match_none(regexp: &str, negative_set: &Vec<String>) -> bool {
-    !negative_set
-        .iter()
-        .any(|x: &String| Regex::new(regexp).unwrap().is_match(x))
+#[cfg_attr(feature = "profiling", flame)]
+pub fn is_redundant(regexp: &Node, positive_set: &[String]) -> bool {
+    // holes need not stand for more than the substrings of the examples
+    let symbols: Vec<char> = infer_alphabet(positive_set, &[]);
+    variants(regexp).iter().any(|v: &Node| {
+        !positive_set
+            .iter()
+            .any(|x: &String| v.accepts(x, Some(&symbols)))
+    })
 }
 
-#[inline(never)]
-#[flame]
-pub fn is_dead(regexp: &String, positive_set: &Vec<String>, negative_set: &Vec<String>) -> bool {
-    let p_regex: &str = &regexp.replace(r"\x00", r".*");
-    let n_regex: &str = &regexp.replace(r"\x00", r".{0}");
-    let pdead: bool = !match_all(p_regex, &positive_set);
-    let ndead: bool = !match_none(n_regex, &negative_set);
+/// Rejects example sets no regex could satisfy, or that stray outside `alphabet`.
+pub fn validate_examples(
+    positive_set: &[String],
+    negative_set: &[String],
+    alphabet: &[char],
+) -> Result<(), SynthError> {
+    for x in positive_set.iter().chain(negative_set.iter()) {
+        if !x.chars().all(|c: char| alphabet.contains(&c)) {
+            return Err(SynthError::InvalidExample(x.clone()));
+        }
+    }
 
-    pdead || ndead
+    let negatives: HashSet<&String> = negative_set.iter().collect();
+    let mut both: Vec<String> = positive_set
+        .iter()
+        .filter(|x: &&String| negatives.contains(x))
+        .cloned()
+        .collect();
+    if !both.is_empty() {
+        both.sort();
+        both.dedup();
+        return Err(SynthError::ContradictoryExamples(both));
+    }
+
+    Ok(())
 }
 
-#[inline(never)]
-#[flame]
-pub fn unroll(regexp: &String) -> String {
-    // TODO: nested asterisk
-    let chars: Vec<char> = regexp.chars().collect();
-    let indices: Vec<(usize, usize)> = find_parentheses(&regexp, false);
-    let mut replacing: HashSet<(String, String)> = HashSet::new();
-    let mut result: String = regexp.clone();
+/// Which examples a complete candidate accepts, in example order, together with the cheapest
+/// cost each such behaviour has been queued at. Candidates that behave alike on every example
+/// either all solve the task or all fail, so only the cheapest one needs to be tried.
+#[derive(Debug, Default)]
+pub struct Signatures {
+    examples: Vec<String>,
+    positives: usize,
+    seen: HashMap<Vec<bool>, usize>,
+    keep_solutions: bool,
+}
 
-    for &(start, end) in indices.iter() {
-        if chars.get(end + 1) == Some(&'*') {
-            let old_str: &str = &regexp[start..end + 1];
-            let new_str: String = format!("{}{}{}", old_str, old_str, old_str);
-            replacing.insert((old_str.to_string(), new_str));
+impl Signatures {
+    pub fn new(positive_set: &[String], negative_set: &[String]) -> Signatures {
+        Signatures {
+            examples: positive_set.iter().chain(negative_set).cloned().collect(),
+            positives: positive_set.len(),
+            seen: HashMap::new(),
+            keep_solutions: false,
         }
     }
 
-    for (old_str, new_str) in &replacing {
-        result = result.replace(old_str, new_str);
+    /// Never drop candidates that solve the task, so that several solutions can be collected.
+    pub fn keep_solutions(mut self, keep: bool) -> Signatures {
+        self.keep_solutions = keep;
+        self
     }
 
-    result
-}
-
-#[inline(never)]
-#[flame]
-pub fn split(regexp: &String) -> Vec<String> {
-    let mut results: Vec<String> = Vec::new();
-    let positions: Vec<(usize, usize)> = find_parentheses(&regexp, true);
+    /// Whether `signature` accepts every positive example and rejects every negative one.
+    pub fn solves(&self, signature: &[bool]) -> bool {
+        signature
+            .iter()
+            .enumerate()
+            .all(|(i, accepted)| *accepted == (i < self.positives))
+    }
 
-    if positions.is_empty() {
-        return vec![regexp.to_string()];
+    #[inline(never)]
+    #[cfg_attr(feature = "profiling", flame)]
+    pub fn signature(&self, regexp: &Node) -> Result<Vec<bool>, SynthError> {
+        let regex: Regex = Regex::new(&regexp.to_string())?;
+        Ok(self
+            .examples
+            .iter()
+            .map(|x: &String| regex.is_match(x))
+            .collect())
     }
 
-    for (start, end) in positions {
-        let or_frag: Vec<String> = regexp[start + 1..end]
-            .split("|")
-            .map(|x| x.to_string())
-            .collect();
-        for x in or_frag {
-            results.push(format!("{}{}{}", &regexp[..start], x, &regexp[end + 1..]));
+    /// Records `signature` at `cost` and returns the bytes it added, or `None` if the same
+    /// behaviour was already queued at most as cheaply.
+    pub fn insert(&mut self, signature: &[bool], cost: usize) -> Option<usize> {
+        if self.keep_solutions && self.solves(signature) {
+            return Some(0);
+        }
+        match self.seen.get_mut(signature) {
+            Some(seen) if *seen <= cost => None,
+            Some(seen) => {
+                *seen = cost;
+                Some(0)
+            }
+            None => {
+                let bytes: usize = std::mem::size_of::<(Vec<bool>, usize)>() + signature.len();
+                self.seen.insert(signature.to_vec(), cost);
+                Some(bytes)
+            }
         }
     }
+}
 
-    results
+/// One expansion of a state's leftmost hole, with the key it is deduplicated by. Complete
+/// expansions carry their signature when signatures are in use.
+struct Expansion {
+    state: State,
+    key: String,
 }
 
+/// Fills `state`'s leftmost hole with every substitution, dropping those that are not in
+/// canonical form when `canonical` is set; returns the rest along with how many were dropped.
+/// Touches no shared state, so it can run on a worker thread.
 #[inline(never)]
-#[flame]
-pub fn is_redundant(regexp: &String, positive_set: &Vec<String>) -> bool {
-    let results: Vec<String> = split(&unroll(&regexp));
+#[cfg_attr(feature = "profiling", flame)]
+fn expand(
+    state: &State,
+    canonical: bool,
+    signatures: Option<&Signatures>,
+    subs: &[(Node, usize)],
+) -> Result<(Vec<Expansion>, usize), SynthError> {
+    let mut expansions: Vec<Expansion> = Vec::new();
+    let mut non_canonical: usize = 0;
+    for (s, cost) in subs {
+        let ext_regexp: Node = state.regexp.fill(s).unwrap();
+        if canonical && !ext_regexp.is_canonical() {
+            non_canonical += 1;
+            continue;
+        }
+        let key: String = ext_regexp.to_string();
+        let mut ext_route: Vec<String> = state.route.clone();
+        ext_route.push(state.regexp.to_string());
 
-    for i in &results {
-        let p_regex: String = i.replace(r"\x00", r".*");
-        if match_none(&p_regex, &positive_set) {
-            return true;
+        let mut extended_state: State = State::new(state.cost + cost, ext_regexp, ext_route);
+        if let Some(signatures) = signatures.filter(|_| extended_state.is_leaf) {
+            extended_state.signature = Some(signatures.signature(&extended_state.regexp)?);
         }
+        expansions.push(Expansion {
+            state: extended_state,
+            key,
+        });
     }
 
-    false
+    Ok((expansions, non_canonical))
 }
 
-#[inline(never)]
-#[flame]
-pub fn extend(pq: &mut Queue, state: &State, table: &mut HashSet<String>) {
-    let index: &usize = &state.regexp.find(r"\x00").unwrap();
-    for (s, cost, pan_dist, pan_backwards) in &ALL_SUB {
-        let ext_regexp: String;
-        let mut ext_parentheses: Vec<(usize, usize)> = state.parentheses.clone();
-        let mut ext_route: Vec<String> = state.route.clone();
-        ext_route.push(state.regexp.clone());
-
-        if is_inside_or(&state, *index) && s == &r"(\x00|\x00)" {
-            ext_regexp = format!(
-                r"{}\x00|\x00{}",
-                &state.regexp[..*index],
-                &state.regexp[index + 4..]
-            );
-            update_parentheses(&mut ext_parentheses, *index, 5, *pan_backwards);
-        } else {
-            ext_regexp = format!(
-                "{}{}{}",
-                &state.regexp[..*index],
-                s,
-                &state.regexp[index + 4..]
-            ); // \x00算四個字元
-            update_parentheses(&mut ext_parentheses, *index, *pan_dist, *pan_backwards);
-            // add new tuple if extending a substitution with parentheses
-            if s == &r"(\x00)*" {
-                ext_parentheses.push((*index, *index + 5))
-            } else if s == &r"(\x00|\x00)" {
-                ext_parentheses.push((*index, *index + 10))
+/// Pushes the `expansions` that have not been seen yet, in order, counting rejected duplicates
+/// and the bytes added to `table` and `signatures` in `stats`.
+fn enqueue(
+    pq: &mut Queue,
+    expansions: Vec<Expansion>,
+    table: &mut HashSet<String>,
+    mut signatures: Option<&mut Signatures>,
+    stats: &mut SynthStats,
+) {
+    for expansion in expansions {
+        if table.contains(&expansion.key) {
+            stats.dedup_hits += 1;
+            continue;
+        }
+        stats.table_bytes += std::mem::size_of::<String>() + expansion.key.capacity();
+        table.insert(expansion.key);
+        if let (Some(signatures), Some(signature)) =
+            (signatures.as_deref_mut(), &expansion.state.signature)
+        {
+            match signatures.insert(signature, expansion.state.cost) {
+                Some(bytes) => stats.table_bytes += bytes,
+                None => {
+                    stats.equivalent += 1;
+                    continue;
+                }
             }
         }
-        if !table.contains(&ext_regexp) {
-            let extended_state: State = State::new(
-                state.cost + cost,
-                ext_regexp.to_string(),
-                ext_parentheses,
-                ext_route,
-            );
-            table.insert(ext_regexp);
-            pq.push(extended_state);
+        pq.push(expansion.state);
+    }
+}
+
+#[inline(never)]
+#[cfg_attr(feature = "profiling", flame)]
+/// Pushes every expansion of `state`'s leftmost hole that has not been seen yet, counting
+/// rejected duplicates and the bytes added to `table` in `stats`. With `canonical`, expansions
+/// that are not in canonical form are dropped, and with `signatures`, so is a complete expansion
+/// when one with the same behaviour on the examples is queued.
+pub fn extend(
+    pq: &mut Queue,
+    state: &State,
+    table: &mut HashSet<String>,
+    canonical: bool,
+    signatures: Option<&mut Signatures>,
+    subs: &[(Node, usize)],
+    stats: &mut SynthStats,
+) -> Result<(), SynthError> {
+    let (expansions, non_canonical): (Vec<Expansion>, usize) =
+        expand(state, canonical, signatures.as_deref(), subs)?;
+    stats.non_canonical += non_canonical;
+    enqueue(pq, expansions, table, signatures, stats);
+    Ok(())
+}
+
+/// What became of one state taken off the queue.
+enum Verdict {
+    Solution,
+    Inconsistent,
+    Dead,
+    Redundant,
+    /// the expansions to enqueue, and how many were not in canonical form
+    Expanded(Vec<Expansion>, usize),
+}
+
+/// States handed to each worker thread at a time in parallel mode.
+const BATCH_PER_THREAD: usize = 64;
+
+/// Hands the spans recorded on the pool's workers to `flame` once the search is over, however
+/// it ends; until then they stay on the workers, which may outlive the report.
+#[cfg(feature = "profiling")]
+struct CommitSpans<'a>(Option<&'a ThreadPool>);
+
+#[cfg(feature = "profiling")]
+impl Drop for CommitSpans<'_> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.0 {
+            pool.broadcast(|_| flame::commit_thread());
         }
     }
 }
 
 #[inline(never)]
-#[flame]
+#[cfg_attr(feature = "profiling", flame)]
 pub fn synth(
-    positive_set: &Vec<String>,
-    negative_set: &Vec<String>,
-    debug: bool,
-    pruning: bool,
-) -> State {
-    let init_state: State = State::new(0, r"^\x00$".to_string(), Vec::new(), Vec::new());
-    let mut pq: Queue = Queue::new(13);
-    let (mut total, mut leaf, mut dead, mut redundant) = (0, 0, 0, 0);
+    positive_set: &[String],
+    negative_set: &[String],
+    alphabet: &[char],
+    grammar: &Grammar,
+    limits: &Limits,
+    options: &SearchOptions,
+) -> Result<SynthOutcome, SynthError> {
+    validate_examples(positive_set, negative_set, alphabet)?;
+    grammar.validate().map_err(SynthError::InvalidGrammar)?;
+    let init_state: State = State::new(0, Node::Hole, Vec::new());
+    let subs: Vec<(Node, usize)> = grammar.substitutions(alphabet);
+    // without a terminal no candidate is ever complete, and the search would never end
+    if !subs.iter().any(|(s, _)| s.is_complete()) {
+        return Err(SynthError::InvalidGrammar(format!(
+            "no production is free of holes over the alphabet {:?}",
+            alphabet
+        )));
+    }
+    // every hole ends up holding at least one terminal, so that is the least it can cost
+    let hole_cost: usize = if options.astar {
+        subs.iter()
+            .filter(|(s, _)| s.is_complete())
+            .map(|(_, cost)| *cost)
+            .min()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let mut pq: Queue = Queue::with_hole_cost(hole_cost);
+    let mut stats: SynthStats = SynthStats::default();
     let mut table: HashSet<String> = HashSet::new();
+    let mut signatures: Signatures = Signatures::new(positive_set, negative_set)
+        .keep_solutions(options.solutions != Solutions::First);
+    let canonical: bool = grammar.is_elementary();
+    // only the cheapest solutions are sure to survive `is_redundant`, and only when the grammar
+    // can derive the regex without the redundant part, which an elementary grammar always can
+    let skip_redundant: bool = matches!(options.solutions, Solutions::Cheapest(_)) || !canonical;
+    let mut solutions: Vec<State> = Vec::new();
+    let pool: Option<ThreadPool> = match options.threads {
+        Some(threads) => Some(
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| SynthError::ThreadPool(e.to_string()))?,
+        ),
+        None => None,
+    };
+    #[cfg(feature = "profiling")]
+    let _spans: CommitSpans = CommitSpans(pool.as_ref());
+    let batch_size: usize = pool.as_ref().map_or(1, |pool: &ThreadPool| {
+        pool.current_num_threads() * BATCH_PER_THREAD
+    });
+
+    // everything but the queue, tables and counters, so that it can run on any thread
+    let examine = |state: &State, signatures: &Signatures| -> Result<Verdict, SynthError> {
+        if state.is_leaf {
+            // `expand` took the signature of every leaf it queued, so the regex is not compiled
+            // again
+            let signature: &[bool] = state
+                .signature
+                .as_deref()
+                .expect("leaves are queued with their signature");
+            if signatures.solves(signature) {
+                return Ok(Verdict::Solution);
+            }
+            return Ok(Verdict::Inconsistent);
+        }
+        if options.pruning {
+            if is_dead(&state.regexp, positive_set, negative_set, alphabet) {
+                return Ok(Verdict::Dead);
+            }
+            if !skip_redundant && is_redundant(&state.regexp, positive_set) {
+                return Ok(Verdict::Redundant);
+            }
+        }
+        let (expansions, non_canonical): (Vec<Expansion>, usize) =
+            expand(state, canonical, Some(signatures), &subs)?;
+        Ok(Verdict::Expanded(expansions, non_canonical))
+    };
 
-    let mut curr_cost: usize = 0;
     let start: Instant = Instant::now();
-    let mut elapsed: f32;
-    println!("cost,sec,state_num");
+    if options.verbose {
+        println!("priority,sec,state_num");
+    }
 
     pq.push(init_state);
-    while !pq.is_empty() {
-        let curr_state: State = pq.pop().unwrap();
-        // if debug {
-        //     println!(
-        //         "{}, {}, {:?}",
-        //         curr_state.cost, curr_state.regexp, curr_state.parentheses
-        //     );
-        // }
-
-        if curr_state.is_leaf {
-            leaf += 1;
-            if match_all(&curr_state.regexp, &positive_set)
-                && match_none(&curr_state.regexp, &negative_set)
+    'search: loop {
+        // a batch shares one priority and is merged back in queue order, so the search takes
+        // the same course whatever the batch size
+        let batch: Vec<State> = pq.pop_batch(batch_size);
+        if batch.is_empty() {
+            break;
+        }
+        let verdicts: Vec<Result<Verdict, SynthError>> = match &pool {
+            Some(pool) => pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|state: &State| examine(state, &signatures))
+                    .collect()
+            }),
+            None => batch
+                .iter()
+                .map(|state: &State| examine(state, &signatures))
+                .collect(),
+        };
+        let mut pending: usize = batch.iter().map(State::footprint).sum();
+
+        for (curr_state, verdict) in batch.into_iter().zip(verdicts) {
+            // states come out in order of a lower bound on the cost of their completions, so
+            // everything left is over the bound too
+            let priority: usize = pq.priority(&curr_state);
+            if limits
+                .max_cost
+                .is_some_and(|max_cost: usize| priority > max_cost)
             {
-                println!(
-                    "Total: {}, Leaf: {}, Dead: {}, Redundant: {}",
-                    total, leaf, dead, redundant
-                );
-                return curr_state.clone();
+                break 'search;
+            }
+            // nothing left can cost as little as the solutions found
+            if options.solutions == Solutions::AllMinimal
+                && solutions.first().is_some_and(|s: &State| priority > s.cost)
+            {
+                break 'search;
             }
-        } else if pruning {
-            if is_dead(&curr_state.regexp, &positive_set, &negative_set) {
-                if debug {
-                    println!("{} is dead", &curr_state.regexp);
+            // running out of budget after a solution ends the search with what was found
+            stats.elapsed = start.elapsed();
+            if let Some(timeout) = limits.timeout {
+                if stats.elapsed >= timeout {
+                    if solutions.is_empty() {
+                        return Err(SynthError::Timeout(timeout, Box::new(stats)));
+                    }
+                    break 'search;
+                }
+            }
+            if let Some(max_states) = limits.max_states {
+                if stats.total >= max_states {
+                    if solutions.is_empty() {
+                        return Err(SynthError::StateLimit(max_states, Box::new(stats)));
+                    }
+                    break 'search;
+                }
+            }
+            // the rest of the batch is still waiting, just as if it were in the queue
+            let memory: usize = pq.footprint() + pending + stats.table_bytes;
+            pending -= curr_state.footprint();
+            stats.peak_memory = stats.peak_memory.max(memory);
+            if let Some(max_memory) = limits.max_memory {
+                if memory > max_memory {
+                    if solutions.is_empty() {
+                        return Err(SynthError::MemoryLimit(max_memory, Box::new(stats)));
+                    }
+                    break 'search;
+                }
+            }
+            if stats.levels.last().map(|l: &LevelStats| l.priority) != Some(priority) {
+                stats.levels.push(LevelStats {
+                    priority,
+                    states: 0,
+                    reached_after: stats.elapsed,
+                });
+            }
+            stats.levels.last_mut().unwrap().states += 1;
+            stats.total += 1;
+
+            match verdict? {
+                Verdict::Solution => {
+                    stats.leaf += 1;
+                    let distinct: bool = !options.distinct_languages
+                        || !solutions.iter().any(|s: &State| {
+                            language::equivalent(&s.regexp, &curr_state.regexp, alphabet)
+                        });
+                    if distinct {
+                        solutions.push(curr_state);
+                    }
+                    let done: bool = match options.solutions {
+                        Solutions::First => true,
+                        Solutions::AllMinimal => false,
+                        Solutions::Cheapest(k) => solutions.len() >= k.get(),
+                    };
+                    if done {
+                        break 'search;
+                    }
+                }
+                Verdict::Inconsistent => stats.leaf += 1,
+                Verdict::Dead => {
+                    if options.debug {
+                        println!("{} is dead", &curr_state.regexp);
+                    }
+                    stats.dead += 1;
                 }
-                dead += 1;
-            } else if is_redundant(&curr_state.regexp, &positive_set) {
-                if debug {
-                    println!("{} is redundant", &curr_state.regexp);
+                Verdict::Redundant => {
+                    if options.debug {
+                        println!("{} is redundant", &curr_state.regexp);
+                    }
+                    stats.redundant += 1;
                 }
-                redundant += 1;
-            } else {
-                extend(&mut pq, &curr_state, &mut table);
+                Verdict::Expanded(expansions, non_canonical) => {
+                    stats.non_canonical += non_canonical;
+                    enqueue(
+                        &mut pq,
+                        expansions,
+                        &mut table,
+                        Some(&mut signatures),
+                        &mut stats,
+                    );
+                }
+            }
+            if options.verbose && stats.levels.last().unwrap().states == 1 && priority > 0 {
+                println!(
+                    "{},{},{}",
+                    priority,
+                    start.elapsed().as_secs_f32(),
+                    stats.total
+                );
             }
-        } else {
-            extend(&mut pq, &curr_state, &mut table);
         }
-        total += 1;
-        // break;
-        if curr_state.cost > curr_cost {
-            elapsed = start.elapsed().as_secs_f32();
-            curr_cost = curr_state.cost;
-            println!("{},{},{}", curr_cost, elapsed, total);
+    }
+    stats.elapsed = start.elapsed();
+    if !solutions.is_empty() {
+        if options.verbose {
+            println!("{}", stats);
         }
+        let mut solutions: std::vec::IntoIter<State> = solutions.into_iter();
+        let mut outcome: SynthOutcome = SynthOutcome::new(solutions.next().unwrap(), stats);
+        outcome.alternatives = solutions.collect();
+        return Ok(outcome);
+    }
+    match limits.max_cost {
+        Some(max_cost) => Err(SynthError::NoSolutionWithinBound(max_cost, Box::new(stats))),
+        None => Err(SynthError::SearchExhausted(Box::new(stats))),
     }
-    State::new(0, "".to_string(), Vec::new(), Vec::new())
 }
 
-pub fn negative_examples(condition: &str, set_len: usize) -> Vec<String> {
-    let charset: &str = "01";
-    let mut examples: Vec<String> = Vec::new();
-    let mut curr_example: String;
+/// A string whose length is drawn uniformly from `lengths` and whose symbols are drawn
+/// uniformly from `charset`.
+pub fn random_string<R: Rng>(rng: &mut R, lengths: Range<usize>, charset: &[char]) -> String {
+    let len: usize = rng.gen_range(lengths);
+    (0..len)
+        .map(|_| charset[rng.gen_range(0..charset.len())])
+        .collect()
+}
 
-    if condition == "start_with_0" {
-        while examples.len() < set_len {
-            curr_example = generate(rand::thread_rng().gen_range(1..50), charset);
-            if !curr_example.starts_with("0") && !examples.contains(&curr_example) {
-                examples.push(curr_example);
-            }
+/// The cost of the cheapest derivation of `regexp` in `grammar`, or `None` when it has none.
+///
+/// An elementary grammar prices the tree directly; any other is searched breadth-first, giving
+/// up with `StateLimit` after `max_states` states.
+#[inline(never)]
+#[cfg_attr(feature = "profiling", flame)]
+pub fn get_cost(
+    regexp: &Node,
+    alphabet: &[char],
+    grammar: &Grammar,
+    max_states: Option<usize>,
+) -> Result<Option<usize>, SynthError> {
+    if grammar.is_elementary() {
+        return Ok(grammar.derivation_cost(regexp, alphabet));
+    }
+    let init_state: State = State::new(0, Node::Hole, Vec::new());
+    let subs: Vec<(Node, usize)> = grammar.substitutions(alphabet);
+    let mut pq: Queue = Queue::new();
+    let mut table: HashSet<String> = HashSet::new();
+    let mut stats: SynthStats = SynthStats::default();
+    let target: String = regexp.to_string();
+    // a derivation needs at most one production per symbol plus one per concatenation
+    let max_cost: usize = 2 * target.len() * grammar.max_cost();
+
+    pq.push(init_state);
+    while let Some(curr_state) = pq.pop() {
+        if curr_state.cost > max_cost {
+            break;
         }
-    } else if condition == "end_with_01" {
-        while examples.len() < set_len {
-            curr_example = generate(rand::thread_rng().gen_range(1..50), charset);
-            if !curr_example.ends_with("01") && !examples.contains(&curr_example) {
-                examples.push(curr_example);
-            }
+        if max_states.is_some_and(|max_states: usize| stats.total >= max_states) {
+            return Err(SynthError::StateLimit(stats.total, Box::new(stats)));
+        }
+        stats.total += 1;
+        if curr_state.regexp.to_string() == target {
+            return Ok(Some(curr_state.cost));
+        } else if !curr_state.is_leaf {
+            // `regexp` need not be canonical, and without signatures nothing can fail
+            extend(
+                &mut pq,
+                &curr_state,
+                &mut table,
+                false,
+                None,
+                &subs,
+                &mut stats,
+            )?;
         }
-    } else if condition == "containing_0101" {
-        while examples.len() < set_len {
-            curr_example = generate(rand::thread_rng().gen_range(1..50), charset);
-            if !curr_example.contains("0101") && !examples.contains(&curr_example) {
-                examples.push(curr_example);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::HOLE;
+    use crate::grammar::ExtraProductions;
+    use crate::{cost, outcome};
+
+    /// A search for the task in `lines` that prints nothing.
+    fn quiet(lines: &str) -> Synthesizer {
+        Synthesizer::from_test_case(&TestCase::from_lines(lines).unwrap()).verbose(false)
+    }
+
+    /// The `start_with_0` task from `cases`.
+    fn start_with_0() -> Synthesizer {
+        quiet("+01\n+01101\n+0001\n-10\n-1\n-11010\n")
+    }
+
+    /// Runs `synthesizer` as it is and with `variant` applied, checking that both find a regex
+    /// of the same cost; returns the plain outcome first.
+    fn same_cost(
+        synthesizer: Synthesizer,
+        variant: impl FnOnce(Synthesizer) -> Synthesizer,
+    ) -> (outcome::SynthOutcome, outcome::SynthOutcome) {
+        let plain: outcome::SynthOutcome = synthesizer.clone().run().unwrap();
+        let varied: outcome::SynthOutcome = variant(synthesizer).run().unwrap();
+        assert_eq!(varied.state.cost, plain.state.cost, "{}", varied.regex);
+        (plain, varied)
+    }
+
+    #[test]
+    fn start_with_0_is_solved() {
+        let ps: Vec<String> = vec!["01".to_string(), "01101".to_string(), "0001".to_string()];
+        let ns: Vec<String> = vec!["10".to_string(), "1".to_string(), "11010".to_string()];
+        let alphabet: Vec<char> = infer_alphabet(&ps, &ns);
+        let state: State = synth(
+            &ps,
+            &ns,
+            &alphabet,
+            &Grammar::default(),
+            &Limits::default(),
+            &SearchOptions::default(),
+        )
+        .unwrap()
+        .state;
+        assert_eq!(state.regexp.to_string(), "^(0(1)*)*$");
+        assert_eq!(state.cost, 5);
+    }
+
+    #[test]
+    fn is_really_redundant() {
+        let s: State = State::new(
+            0,
+            Node::star(Node::concat(Node::Literal('0'), Node::Hole)),
+            vec![],
+        );
+        assert_eq!(s.regexp.to_string(), r"^(0\x00)*$");
+        assert!(!is_redundant(
+            &s.regexp,
+            &["01", "01101", "0001"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect::<Vec<String>>(),
+        ));
+    }
+
+    #[test]
+    fn alphabet_is_inferred_and_escaped() {
+        let tc: TestCase = TestCase::new(
+            ["a.b", "a.a"].iter().map(|&x| x.to_string()).collect(),
+            ["ab"].iter().map(|&x| x.to_string()).collect(),
+        );
+        assert_eq!(tc.alphabet, vec!['.', 'a', 'b']);
+
+        let terminals: Vec<String> = tc
+            .grammar
+            .substitutions(&tc.alphabet)
+            .into_iter()
+            .map(|(s, _)| s.render(HOLE))
+            .take(3)
+            .collect();
+        assert_eq!(terminals, vec![r"\.", "a", "b"]);
+    }
+
+    #[test]
+    fn synth_over_custom_alphabet() {
+        let ps: Vec<String> = ["a.", "a.."].iter().map(|&x| x.to_string()).collect();
+        let ns: Vec<String> = ["a", "ab.", "."].iter().map(|&x| x.to_string()).collect();
+        let state: State = synth(
+            &ps,
+            &ns,
+            &['.', 'a', 'b'],
+            &Grammar::default(),
+            &Limits::default(),
+            &SearchOptions::default(),
+        )
+        .unwrap()
+        .state;
+        assert_eq!(state.regexp.to_string(), r"^a\.(\.)*$");
+    }
+
+    #[test]
+    fn synth_with_extra_productions() {
+        let mut tc: TestCase = TestCase::new(
+            ["10", "110", "1110"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+            ["0", "1", "100", "11"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+        );
+        tc.grammar = Grammar::new(
+            &ExtraProductions {
+                plus: true,
+                optional: true,
+                wildcard: true,
+                classes: vec![vec!['0', '1']],
+            },
+            &cost::CostModel::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            tc.synth(&SearchOptions::default()).unwrap().regex,
+            r"^(1)+0$"
+        );
+    }
+
+    #[test]
+    fn bad_examples_are_reported() {
+        let ps: Vec<String> = ["01", "10"].iter().map(|&x| x.to_string()).collect();
+        let ns: Vec<String> = ["10", "2"].iter().map(|&x| x.to_string()).collect();
+        let g: Grammar = Grammar::default();
+        assert_eq!(
+            synth(
+                &ps,
+                &ns,
+                &['0', '1'],
+                &g,
+                &Limits::default(),
+                &SearchOptions::default()
+            ),
+            Err(outcome::SynthError::InvalidExample("2".to_string()))
+        );
+        assert_eq!(
+            synth(
+                &ps,
+                &ns,
+                &['0', '1', '2'],
+                &g,
+                &Limits::default(),
+                &SearchOptions::default()
+            ),
+            Err(outcome::SynthError::ContradictoryExamples(vec![
+                "10".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn queue_grows_past_the_old_ceiling() {
+        let mut pq: Queue = Queue::new();
+        pq.push(State::new(40, Node::Hole, Vec::new()));
+        pq.push(State::new(3, Node::Literal('0'), Vec::new()));
+        assert_eq!(pq.pop().unwrap().cost, 3);
+        assert_eq!(pq.pop().unwrap().cost, 40);
+        assert!(pq.is_empty());
+        assert!(pq.pop().is_none());
+    }
+
+    #[test]
+    fn astar_keeps_the_minimal_cost() {
+        let mut pq: Queue = Queue::with_hole_cost(2);
+        let open: State = State::new(3, Node::concat(Node::Hole, Node::Hole), Vec::new());
+        assert_eq!(pq.priority(&open), 7);
+        pq.push(open);
+        pq.push(State::new(6, Node::Literal('0'), Vec::new()));
+        assert_eq!(pq.pop().unwrap().cost, 6);
+
+        let pricey_literals: Grammar = Grammar::new(
+            &ExtraProductions::default(),
+            &cost::CostModel {
+                literal: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let tasks: [Synthesizer; 3] = [
+            start_with_0(),
+            quiet("+1\n+01\n-11\n-0\n").grammar(pricey_literals),
+            quiet("+\n+01\n+0101\n-0\n-1\n-10\n"),
+        ];
+        for synthesizer in tasks {
+            let (plain, astar): (outcome::SynthOutcome, outcome::SynthOutcome) =
+                same_cost(synthesizer, |s: Synthesizer| s.astar(true));
+            assert!(astar.stats.total < plain.stats.total);
+        }
+    }
+
+    #[test]
+    fn parallel_search_takes_the_same_course() {
+        let counters = |stats: &outcome::SynthStats| {
+            (
+                stats.total,
+                stats.leaf,
+                stats.dead,
+                stats.redundant,
+                stats.dedup_hits,
+                stats.equivalent,
+                stats.non_canonical,
+                stats.peak_memory,
+            )
+        };
+        for (pruning, astar) in [(false, false), (true, false), (true, true)] {
+            let synthesizer: Synthesizer = start_with_0().pruning(pruning).astar(astar);
+            let sequential: outcome::SynthOutcome = synthesizer.clone().run().unwrap();
+            let parallel: outcome::SynthOutcome = synthesizer.threads(3).run().unwrap();
+            assert_eq!(parallel.regex, sequential.regex);
+            assert_eq!(parallel.state.route, sequential.state.route);
+            assert_eq!(counters(&parallel.stats), counters(&sequential.stats));
+        }
+
+        let mut tc: TestCase = TestCase::from_lines("+01\n+0001\n-10\n").unwrap();
+        tc.verbose = false;
+        tc.limits.max_states = Some(50);
+        let options: SearchOptions = SearchOptions {
+            threads: Some(2),
+            ..Default::default()
+        };
+        match tc.synth(&options) {
+            Err(outcome::SynthError::StateLimit(50, stats)) => assert_eq!(stats.total, 50),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn several_solutions_are_collected() {
+        let ab: [char; 2] = ['0', '1'];
+        let tc: TestCase = TestCase::from_lines("+01\n+10\n-0\n-1\n").unwrap();
+        for (pruning, astar) in [(false, false), (true, true)] {
+            let outcome: outcome::SynthOutcome = Synthesizer::from_test_case(&tc)
+                .solutions(crate::Solutions::AllMinimal)
+                .pruning(pruning)
+                .astar(astar)
+                .verbose(false)
+                .run()
+                .unwrap();
+            assert_eq!(outcome.regexes(), vec!["^(0|1)(0|1)$", "^(01|10)$"]);
+            assert_eq!(outcome.alternatives[0].cost, outcome.state.cost);
+        }
+
+        let top = |pruning: bool, distinct: bool| {
+            start_with_0()
+                .solutions(crate::Solutions::Cheapest(NonZeroUsize::new(5).unwrap()))
+                .pruning(pruning)
+                .distinct_languages(distinct)
+                .astar(true)
+                .run()
+                .unwrap()
+        };
+        let all: outcome::SynthOutcome = top(false, false);
+        assert_eq!(all.regex, "^(0(1)*)*$");
+        assert_eq!(all.alternatives.len(), 4);
+        assert!(all.alternatives.windows(2).all(|w| w[0].cost <= w[1].cost));
+        // `is_redundant` is off here, as it may prune solutions that are not the cheapest
+        assert_eq!(top(true, false).regexes(), all.regexes());
+
+        let distinct: outcome::SynthOutcome = top(false, true);
+        let states: Vec<&State> = std::iter::once(&distinct.state)
+            .chain(&distinct.alternatives)
+            .collect();
+        assert_eq!(states.len(), 5);
+        for (i, a) in states.iter().enumerate() {
+            for b in &states[i + 1..] {
+                assert!(!language::equivalent(&a.regexp, &b.regexp, &ab));
             }
         }
-    } else if condition == "begin_1_end_0" {
-        while examples.len() < set_len {
-            curr_example = generate(rand::thread_rng().gen_range(1..50), charset);
-            if !curr_example.starts_with("1") || !curr_example.ends_with("0") {
-                if !examples.contains(&curr_example) {
-                    examples.push(curr_example);
-                }
+    }
+
+    #[test]
+    fn runs_are_reproducible() {
+        let outcome: outcome::SynthOutcome = start_with_0().pruning(true).run().unwrap();
+        let states: Vec<usize> = outcome.stats.levels.iter().map(|l| l.states).collect();
+
+        assert_eq!(outcome.regex, "^(0(1)*)*$");
+        assert_eq!(
+            outcome.state.route,
+            [
+                "^\\x00$",
+                "^(\\x00)*$",
+                "^(\\x00\\x00)*$",
+                "^(0\\x00)*$",
+                "^(0(\\x00)*)*$"
+            ]
+        );
+        assert_eq!(outcome.stats.total, 157);
+        assert_eq!(states, [1, 5, 11, 35, 104, 1]);
+    }
+
+    #[test]
+    fn max_cost_bounds_the_search() {
+        let mut tc: TestCase = TestCase::new(
+            ["101", "001101101", "0110001"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+            ["100101011", "110000", "00111010"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+        );
+        tc.limits.max_cost = Some(6);
+        assert!(matches!(
+            tc.synth(&SearchOptions::default()),
+            Err(outcome::SynthError::NoSolutionWithinBound(6, _))
+        ));
+        tc.limits.max_cost = Some(7);
+        assert_eq!(tc.synth(&SearchOptions::default()).unwrap().state.cost, 7);
+    }
+
+    #[test]
+    fn stats_are_returned_as_data() {
+        let mut tc: TestCase = TestCase::new(
+            ["101", "001101101", "0110001"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+            ["100101011", "110000", "00111010"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+        );
+        tc.verbose = false;
+        let stats: outcome::SynthStats = tc.synth(&SearchOptions::default()).unwrap().stats;
+        let priorities: Vec<usize> = stats.levels.iter().map(|l| l.priority).collect();
+        assert_eq!(priorities, (0..=7).collect::<Vec<usize>>());
+        assert_eq!(
+            stats.levels.iter().map(|l| l.states).sum::<usize>(),
+            stats.total
+        );
+        assert!(stats.leaf > 0 && stats.dedup_hits > 0);
+
+        tc.limits.max_cost = Some(3);
+        let e: outcome::SynthError = tc.synth(&SearchOptions::default()).unwrap_err();
+        assert_eq!(e.stats().unwrap().levels.last().unwrap().priority, 3);
+    }
+
+    #[test]
+    fn budgets_stop_the_search() {
+        let mut tc: TestCase = TestCase::new(
+            ["101", "001101101", "0110001"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+            ["100101011", "110000", "00111010"]
+                .iter()
+                .map(|&x| x.to_string())
+                .collect(),
+        );
+        tc.limits.max_states = Some(100);
+        match tc.synth(&SearchOptions::default()) {
+            Err(outcome::SynthError::StateLimit(100, stats)) => assert_eq!(stats.total, 100),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        tc.limits = Limits {
+            timeout: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        };
+        assert!(matches!(
+            tc.synth(&SearchOptions::default()),
+            Err(outcome::SynthError::Timeout(..))
+        ));
+
+        tc.limits = Limits {
+            max_memory: Some(10_000),
+            ..Default::default()
+        };
+        match tc.synth(&SearchOptions::default()) {
+            Err(outcome::SynthError::MemoryLimit(10_000, stats)) => {
+                assert!(stats.peak_memory > 10_000)
             }
+            other => panic!("unexpected {:?}", other),
         }
     }
 
-    examples
-}
+    #[test]
+    fn redundancy_reaches_nested_stars_and_alternations() {
+        let ps: Vec<String> = ["0", "00"].iter().map(|&x| x.to_string()).collect();
+        // a class is kept whole, since the grammar need not have a class without `1`
+        let class: Node = Node::concat(Node::Class(vec!['0', '1']), Node::Hole);
+        assert!(!is_redundant(&class, &ps));
+        let optional: Node = Node::concat(Node::optional(Node::Literal('1')), Node::Hole);
+        assert!(is_redundant(&optional, &ps));
+        let plus: Node = Node::plus(Node::concat(Node::Literal('0'), Node::Hole));
+        assert!(!is_redundant(&plus, &ps));
+        // `(1)*` inside `(0(1)*)*` is never used
+        let nested_star: Node = Node::parse(r"(0(1)*)*").unwrap();
+        assert!(is_redundant(&nested_star, &ps));
 
-#[inline(never)]
-#[flame]
-pub fn get_cost(regexp: String) -> usize {
-    let init_state: State = State::new(0, r"^\x00$".to_string(), Vec::new(), Vec::new());
-    let mut pq: Queue = Queue::new(100);
-    let mut table: HashSet<String> = HashSet::new();
+        // the `1` inside `((0|1)0|\x00)*` is never used
+        let ps: Vec<String> = ["00", "1", "001"].iter().map(|&x| x.to_string()).collect();
+        let nested_alt: Node = Node::parse(r"(((0|1)0|\x00))*").unwrap();
+        assert!(is_redundant(&nested_alt, &ps));
+        let ps: Vec<String> = ["10", "001"].iter().map(|&x| x.to_string()).collect();
+        assert!(!is_redundant(&nested_alt, &ps));
 
-    pq.push(init_state);
-    while !pq.is_empty() {
-        let curr_state: State = pq.pop().unwrap();
-        if curr_state.regexp == regexp {
-            return curr_state.cost;
-        } else if !curr_state.is_leaf {
-            extend(&mut pq, &curr_state, &mut table);
+        // `(0)*1` is the cheapest regex for these, so its prefix must survive
+        let ps: Vec<String> = ["1", "01"].iter().map(|&x| x.to_string()).collect();
+        let prefix: Node = Node::parse(r"(0)*\x00").unwrap();
+        assert!(!is_redundant(&prefix, &ps));
+    }
+
+    #[test]
+    fn equivalent_candidates_are_queued_once() {
+        let ps: Vec<String> = vec!["0".to_string(), "00".to_string()];
+        let ns: Vec<String> = vec!["".to_string(), "1".to_string()];
+        let mut signatures: Signatures = Signatures::new(&ps, &ns);
+        let a: Vec<bool> = signatures
+            .signature(&Node::parse("(0)*0").unwrap())
+            .unwrap();
+        let b: Vec<bool> = signatures
+            .signature(&Node::parse("0(0)*").unwrap())
+            .unwrap();
+        assert_eq!(a, vec![true, true, false, false]);
+        assert_eq!(a, b);
+        assert!(signatures.insert(&a, 5).is_some());
+        assert!(signatures.insert(&b, 5).is_none());
+        // a cheaper candidate with the same behaviour is still queued
+        assert!(signatures.insert(&b, 4).is_some());
+
+        let outcome: outcome::SynthOutcome = quiet("+0\n+00\n-\n-1\n").run().unwrap();
+        assert_eq!(outcome.regex, "^0(0)*$");
+        assert!(outcome.stats.equivalent > 0);
+        // the solution was recognised by the signature taken when it was queued
+        assert_eq!(
+            outcome.state.signature,
+            Some(vec![true, true, false, false])
+        );
+    }
+
+    #[test]
+    fn is_dead_approximates_holes_soundly() {
+        let ps: Vec<String> = ["01"].iter().map(|&x| x.to_string()).collect();
+        let ns: Vec<String> = ["0"].iter().map(|&x| x.to_string()).collect();
+        // `0\x00` can still become `01`, even though `0` alone is a negative
+        let open: Node = Node::concat(Node::Literal('0'), Node::Hole);
+        assert!(!is_dead(&open, &ps, &ns, &['0', '1']));
+        // every completion of `0(\x00)*` accepts `0`
+        let starred: Node = Node::concat(Node::Literal('0'), Node::star(Node::Hole));
+        assert!(is_dead(&starred, &ps, &ns, &['0', '1']));
+        // a hole only stands for strings over the alphabet
+        assert!(is_dead(&open, &ps, &ns, &['0']));
+    }
+
+    #[test]
+    fn pruning_keeps_the_minimal_solution() {
+        let with_plus: Grammar = Grammar::new(
+            &ExtraProductions {
+                plus: true,
+                optional: true,
+                ..Default::default()
+            },
+            &cost::CostModel::default(),
+        )
+        .unwrap();
+        // `((0)?1)?1` cannot lose its `?` here, as the grammar has no plain concatenation
+        let optional_prefix: Grammar = Grammar::from_toml(
+            "literal_cost = 1\n[[production]]\npattern = '(\\x00)?\\x00'\ncost = 1\n\
+             [[production]]\npattern = '(\\x00)*'\ncost = 1\n",
+        )
+        .unwrap();
+        assert!(!optional_prefix.is_elementary());
+        let tasks: [Synthesizer; 9] = [
+            start_with_0(),
+            start_with_0().grammar(with_plus.clone()),
+            // `.{0}` holes used to prune every prefix of `^((1)*0)*1$`
+            quiet("+101\n+001101101\n+0110001\n-100101011\n-110000\n-00111010\n"),
+            quiet("+01\n+1010111011101011101011101\n+01111\n+1101\n-0000\n-0110001\n"),
+            // unrolling `x*` into `x x x*` used to prune every prefix of `^(0)*1$`
+            quiet("+1\n+01\n-11\n-0\n"),
+            quiet("+0\n+1\n-\n-01\n").grammar(with_plus.clone()),
+            quiet("+\n+0\n+00\n-1\n-01\n").grammar(with_plus.clone()),
+            quiet("+10\n+110\n+1110\n-0\n-1\n-100\n-11\n").grammar(with_plus),
+            quiet("+011\n-\n-01\n-0\n-10\n").grammar(optional_prefix),
+        ];
+
+        for synthesizer in tasks {
+            let (full, pruned): (outcome::SynthOutcome, outcome::SynthOutcome) =
+                same_cost(synthesizer, |s: Synthesizer| s.pruning(true));
+            assert!(pruned.stats.total <= full.stats.total);
         }
     }
-    0
 }